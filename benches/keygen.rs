@@ -0,0 +1,30 @@
+// benches/keygen.rs
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::hint::black_box;
+use vanityssh_rust::{keygen, ssh};
+
+fn bench_generate(c: &mut Criterion) {
+    c.bench_function("generate_key_pair", |b| b.iter(keygen::generate_key_pair));
+    c.bench_function("generate_openssh_key_pair", |b| {
+        b.iter(|| keygen::generate_openssh_key_pair(black_box(Some("bench@example"))))
+    });
+}
+
+fn bench_encode_private_key(c: &mut Criterion) {
+    let signing_key = ed25519_dalek::SigningKey::from_bytes(&[7u8; 32]);
+    let public_key = signing_key.verifying_key().to_bytes();
+    let private_key = signing_key.to_bytes();
+
+    c.bench_function("encode_ssh_private_key", |b| {
+        b.iter(|| {
+            ssh::private_key::encode_ssh_private_key(
+                black_box(&public_key),
+                black_box(&private_key),
+                Some("bench@example"),
+            )
+        })
+    });
+}
+
+criterion_group!(benches, bench_generate, bench_encode_private_key);
+criterion_main!(benches);
@@ -0,0 +1,115 @@
+// benches/matching.rs
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::hint::black_box;
+use vanityssh_rust::thread_pool::ThreadPoolConfig;
+use vanityssh_rust::{keygen, matcher, ssh};
+
+/// Compares an anchored prefix with a pattern that can match anywhere
+fn bench_matches_pattern(c: &mut Criterion) {
+    let (public_key, _) = keygen::generate_openssh_key_pair(None).unwrap();
+    let body = ssh::public_key::extract_ssh_key_data(&public_key).unwrap();
+
+    let mut group = c.benchmark_group("matches_pattern");
+    group.bench_function("anchored", |b| {
+        b.iter(|| matcher::matches_pattern(black_box(&body), "^AAAAC3Nzabc", false))
+    });
+    group.bench_function("unanchored", |b| {
+        b.iter(|| matcher::matches_pattern(black_box(&body), "abc", false))
+    });
+    group.finish();
+}
+
+/// Compares the `^literal` fast path with the regex it stands in for
+fn bench_anchored_prefix(c: &mut Criterion) {
+    let (public_key, _) = keygen::generate_openssh_key_pair(None).unwrap();
+    let body = ssh::public_key::extract_ssh_key_data(&public_key).unwrap();
+
+    let mut group = c.benchmark_group("anchored_prefix");
+    group.bench_function("literal", |b| {
+        b.iter(|| matcher::matches_pattern(black_box(&body), "^AAAAC3Nzabc", false))
+    });
+    // The group keeps the pattern off the fast path without changing it
+    group.bench_function("regex", |b| {
+        b.iter(|| matcher::matches_pattern(black_box(&body), "^(AAAAC3Nzabc)", false))
+    });
+    group.finish();
+}
+
+/// Compares compiling the pattern for every key with compiling it once
+fn bench_compiled_pattern(c: &mut Criterion) {
+    let (public_key, _) = keygen::generate_openssh_key_pair(None).unwrap();
+    let body = ssh::public_key::extract_ssh_key_data(&public_key).unwrap();
+    let pattern = "(cafe|f00d)$";
+    let regex = matcher::compile_pattern(pattern, false).unwrap();
+
+    let mut group = c.benchmark_group("unanchored_pattern");
+    group.bench_function("compile_per_key", |b| {
+        b.iter(|| matcher::matches_pattern(black_box(&body), pattern, false))
+    });
+    group.bench_function("compiled_once", |b| {
+        b.iter(|| matcher::matches_compiled(black_box(&body), &regex))
+    });
+    group.finish();
+}
+
+/// Compares encoding every candidate in full with encoding only its body
+fn bench_candidate_encoding(c: &mut Criterion) {
+    let regex = matcher::compile_pattern("^AAAAC3Nzabc", false).unwrap();
+
+    let mut group = c.benchmark_group("candidate");
+    group.bench_function("full_key_pair", |b| {
+        let mut rng = keygen::KeyRng::os();
+        b.iter(|| {
+            let (public_key, _) = keygen::generate_openssh_key_pair_with(&mut rng, None).unwrap();
+            let body = ssh::public_key::extract_ssh_key_data(&public_key).unwrap();
+            matcher::matches_compiled(&body, &regex)
+        })
+    });
+    group.bench_function("body_only", |b| {
+        let mut rng = keygen::KeyRng::os();
+        let mut body = String::new();
+        b.iter(|| {
+            let candidate = keygen::Ed25519Candidate::generate(&mut rng);
+            body.clear();
+            candidate.encode_body(&mut body);
+            matcher::matches_compiled(&body, &regex)
+        })
+    });
+    group.finish();
+}
+
+/// Compares a worker search for a plain prefix, which rules keys out from
+/// their raw bytes, with the same prefix behind a group, which encodes and
+/// regex-matches every key
+fn bench_worker_prefix(c: &mut Criterion) {
+    // No key matches: the first character after the header is 'A'..='P'
+    let search = |pattern: &str| ThreadPoolConfig {
+        pattern: pattern.to_string(),
+        max_attempts: Some(1000),
+        ..Default::default()
+    };
+    let literal = search("^AAAAC3NzaC1lZDI1NTE5AAAAIz");
+    let regex = search("^(AAAAC3NzaC1lZDI1NTE5AAAAIz)");
+
+    let mut group = c.benchmark_group("worker_prefix");
+    group.sample_size(20);
+    group.bench_function("raw_prefix", |b| {
+        let mut rng = keygen::KeyRng::os();
+        b.iter(|| vanityssh_rust::find_key_inline_with(&literal, &mut rng).unwrap())
+    });
+    group.bench_function("regex", |b| {
+        let mut rng = keygen::KeyRng::os();
+        b.iter(|| vanityssh_rust::find_key_inline_with(&regex, &mut rng).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_matches_pattern,
+    bench_anchored_prefix,
+    bench_compiled_pattern,
+    bench_candidate_encoding,
+    bench_worker_prefix
+);
+criterion_main!(benches);
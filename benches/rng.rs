@@ -0,0 +1,21 @@
+// benches/rng.rs
+use criterion::{criterion_group, criterion_main, Criterion};
+use vanityssh_rust::keygen::{self, KeyRng};
+
+/// Compares drawing every key from the OS with a ChaCha20 generator seeded
+/// once from the OS, as the workers do
+fn bench_key_rng(c: &mut Criterion) {
+    let mut group = c.benchmark_group("key_rng");
+    group.bench_function("os", |b| {
+        let mut rng = KeyRng::os();
+        b.iter(|| keygen::Ed25519Candidate::generate(&mut rng))
+    });
+    group.bench_function("chacha20_os_seeded", |b| {
+        let mut rng = KeyRng::os_seeded();
+        b.iter(|| keygen::Ed25519Candidate::generate(&mut rng))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_key_rng);
+criterion_main!(benches);
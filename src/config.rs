@@ -1,119 +1,644 @@
 // src/config.rs
+use crate::error::{ConfigError, Result, VanityError};
+use crate::keygen::KeyAlgorithm;
+use crate::matcher::{Encoding, MatchScope, MatchTarget};
+use crate::ssh::PrivateKeyFormat;
+use crate::OutputFormat;
+use clap::builder::BoolishValueParser;
+use clap::{CommandFactory, FromArgMatches, Parser};
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::path::Path;
 use std::process;
 
+/// Options read from a TOML file given with `--config`. Flags given on the
+/// command line take precedence.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OwnedConfig {
+    pub pattern: Option<String>,
+    pub streaming: Option<bool>,
+    pub case_sensitive: Option<bool>,
+    pub comment: Option<String>,
+    pub threads: Option<usize>,
+}
+
+/// The command-line arguments, as parsed by clap.
+///
+/// Most programs want the `Config` built from these by `Config::from_cli`,
+/// which merges in a `--config` file. Every option takes its value either
+/// as the next argument or after `=`, e.g. `--threads=4`. `--threads`,
+/// `--comment`, `--streaming` and `--case-sensitive` fall back to the
+/// `VANITYSSH_THREADS`, `VANITYSSH_COMMENT`, `VANITYSSH_STREAMING` and
+/// `VANITYSSH_CASE_SENSITIVE` environment variables.
+#[derive(Debug, Default, Parser)]
+#[command(
+    name = "vanityssh-rust",
+    version,
+    about = "VanitySSH - Generate SSH keys with custom patterns",
+    long_about = None,
+    override_usage = "vanityssh-rust <pattern> [OPTIONS]",
+    args_override_self = true,
+    allow_negative_numbers = true
+)]
+pub struct Cli {
+    // Patterns in command-line order, positional ones and `--pattern`
+    // values alike
+    /// Regex pattern to match against the generated keys; give several to
+    /// match any of them
+    #[arg(value_name = "PATTERN")]
+    pub patterns: Vec<String>,
+    // `--pattern` values; `try_parse_args` moves them into `patterns`
+    /// Add a pattern, like a positional one; may be repeated
+    #[arg(long = "pattern", value_name = "PATTERN")]
+    pub pattern_options: Vec<String>,
+    /// Continue generating keys after a match is found
+    #[arg(
+        long,
+        env = "VANITYSSH_STREAMING",
+        value_parser = BoolishValueParser::new(),
+        overrides_with = "no_streaming"
+    )]
+    pub streaming: bool,
+    /// Stop at the first match, even if the config file sets streaming
+    #[arg(long, overrides_with = "streaming")]
+    pub no_streaming: bool,
+    /// Stop after finding N matches
+    #[arg(short = 'n', long, value_name = "N", value_parser = positive_u64)]
+    pub count: Option<u64>,
+    /// Make pattern matching case-sensitive (default is case-insensitive)
+    #[arg(
+        long,
+        visible_alias = "exact-case",
+        env = "VANITYSSH_CASE_SENSITIVE",
+        value_parser = BoolishValueParser::new(),
+        overrides_with = "no_case_sensitive"
+    )]
+    pub case_sensitive: bool,
+    /// Match case-insensitively, even if the config file sets case_sensitive
+    #[arg(long, overrides_with = "case_sensitive")]
+    pub no_case_sensitive: bool,
+    /// Match case-insensitively, but report whether each match is also in exact
+    /// case
+    #[arg(long)]
+    pub prefer_exact: bool,
+    /// Count keys that match PATTERN but not the search pattern, e.g. a shorter
+    /// prefix
+    #[arg(long, value_name = "PATTERN")]
+    pub near: Option<String>,
+    /// Add a comment to the SSH public key
+    #[arg(short, long, env = "VANITYSSH_COMMENT")]
+    pub comment: Option<String>,
+    /// Number of threads to use (default: number of CPU cores)
+    #[arg(short, long, value_name = "N", value_parser = positive_usize, env = "VANITYSSH_THREADS")]
+    pub threads: Option<usize>,
+    /// Append each matched public key to an authorized_keys file
+    #[arg(long, visible_alias = "append-authorized", value_name = "FILE")]
+    pub append_authorized_keys: Option<String>,
+    /// Prefix appended keys with authorized_keys options, e.g.
+    /// no-port-forwarding
+    #[arg(long, value_name = "OPTIONS")]
+    pub key_options: Option<String>,
+    /// End written public key lines with a newline (default)
+    #[arg(long, overrides_with = "no_pub_trailing_newline")]
+    pub pub_trailing_newline: bool,
+    /// Don't end written public key lines with a newline
+    #[arg(long, overrides_with = "pub_trailing_newline")]
+    pub no_pub_trailing_newline: bool,
+    /// Start each worker thread MS milliseconds after the previous one
+    #[arg(long, value_name = "MS", value_parser = milliseconds)]
+    pub stagger_startup: Option<u64>,
+    /// Also print the public key as an Ansible authorized_key task
+    #[arg(long)]
+    pub ansible: bool,
+    /// With --output-dir, print an ssh-keygen command to sign each saved key
+    #[arg(long)]
+    pub cert_template: bool,
+    /// In streaming mode, keep searching and drop matches the output can't keep
+    /// up with
+    #[arg(long)]
+    pub drop_when_full: bool,
+    /// Benchmark the CPU cores and pin workers to the fastest ones
+    #[arg(long)]
+    pub prefer_fast_cores: bool,
+    /// Reject keys containing offensive words from a built-in blocklist
+    #[arg(long)]
+    pub safe: bool,
+    /// Exit with an error instead of warning when no key can match a pattern
+    #[arg(long)]
+    pub strict: bool,
+    /// Key type: ed25519 (default), rsa or rsa:<BITS> (RSA is far slower)
+    #[arg(long, value_name = "ALG")]
+    pub algorithm: Option<KeyAlgorithm>,
+    /// Print each match and the final metrics as a line of JSON
+    #[arg(long)]
+    pub json: bool,
+    /// Print the final metrics as text (default), json or csv
+    #[arg(long, value_name = "FMT")]
+    pub output_format: Option<OutputFormat>,
+    /// Print only the matched public and private key
+    #[arg(short, long)]
+    pub quiet: bool,
+    /// Log each match to stderr; -vv also logs every status update
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+    /// Don't draw the progress display (also off when stderr isn't a terminal)
+    #[arg(long)]
+    pub no_progress: bool,
+    /// Check each matched public key for degenerate values
+    #[arg(long)]
+    pub assess: bool,
+    /// Print the randomart of each match's fingerprint, like ssh-keygen -lv
+    #[arg(long)]
+    pub randomart: bool,
+    /// Check that each match signs and verifies a test message before printing
+    /// it
+    #[arg(long)]
+    pub verify: bool,
+    /// Write private keys as openssh (default), pkcs8 PEM or PuTTY ppk (ed25519
+    /// only)
+    #[arg(long, value_name = "FMT")]
+    pub private_format: Option<PrivateKeyFormat>,
+    /// Validate the options and print the difficulty estimate without searching
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Write NDJSON progress events to a FIFO at PATH (Unix only)
+    #[arg(long, value_name = "PATH")]
+    pub progress_fifo: Option<String>,
+    /// Keep Prometheus metrics for the search in PATH, updated twice a second
+    #[arg(long, value_name = "PATH")]
+    pub metrics_file: Option<String>,
+    /// Save each match as id_ed25519_<n> and id_ed25519_<n>.pub in DIR
+    #[arg(short, long, value_name = "DIR")]
+    pub output_dir: Option<String>,
+    /// Derive keys deterministically from FILE (INSECURE, for testing only)
+    #[arg(long, value_name = "FILE")]
+    pub seed_file: Option<String>,
+    /// Also match any pattern in FILE, one per line (# starts a comment)
+    #[arg(long, value_name = "FILE")]
+    pub wordlist: Option<String>,
+    /// Match keys whose variable part starts with TEXT, instead of a pattern
+    #[arg(long, value_name = "TEXT")]
+    pub prefix: Option<String>,
+    /// Match keys ending with TEXT; with --prefix, both must hold
+    #[arg(long, value_name = "TEXT")]
+    pub suffix: Option<String>,
+    /// Derive keys deterministically from the number N (INSECURE, for testing
+    /// only)
+    #[arg(long, value_name = "N", value_parser = non_negative_u64)]
+    pub seed: Option<u64>,
+    /// Only accept keys whose variable part sorts in [LOW, HIGH)
+    #[arg(long, num_args = 2, value_names = ["LOW", "HIGH"])]
+    pub range: Option<Vec<String>>,
+    /// Match against the SHA256 fingerprint instead of the key body
+    #[arg(long)]
+    pub match_fingerprint: bool,
+    /// Match against the key body (default), the base64 fingerprint or its hex
+    /// (fingerprint-hex)
+    #[arg(long = "match", value_name = "TARGET")]
+    pub match_target: Option<MatchTarget>,
+    /// Match against base64 (default, the key body), base32 or hex of the raw
+    /// key
+    #[arg(long, visible_alias = "format", value_name = "ENC")]
+    pub encoding: Option<Encoding>,
+    /// Match against the key body (default) or the full "ssh-ed25519 AAAA..."
+    /// line
+    #[arg(long, value_name = "SCOPE")]
+    pub match_scope: Option<MatchScope>,
+    /// Only match against the first N characters of the key body
+    #[arg(long, value_name = "N", value_parser = positive_usize)]
+    pub match_length: Option<usize>,
+    /// Give up after generating about N keys
+    #[arg(long, value_name = "N", value_parser = positive_u64)]
+    pub max_attempts: Option<u64>,
+    /// Generate at most about KEYS keys per second in total, e.g. to limit heat
+    #[arg(long, value_name = "KEYS", value_parser = positive_rate)]
+    pub max_rate: Option<f64>,
+    /// Keys each thread generates between progress reports (default: 50)
+    #[arg(long, value_name = "N", value_parser = positive_u64)]
+    pub report_interval: Option<u64>,
+    /// Stop the search after SECS seconds, keeping any matches found
+    #[arg(long, value_name = "SECS", value_parser = positive_seconds)]
+    pub timeout: Option<u64>,
+    /// Write a JSON receipt of the generation parameters to FILE
+    #[arg(long, value_name = "FILE")]
+    pub receipt_file: Option<String>,
+    /// Keep lifetime attempt and match totals in FILE, continuing them across
+    /// restarts
+    #[arg(long, value_name = "FILE")]
+    pub state_file: Option<String>,
+    /// Run in the background in streaming mode (Unix only)
+    #[arg(long)]
+    pub daemon: bool,
+    /// Stream matches, and switch to each new pattern typed on stdin
+    #[arg(long)]
+    pub interactive: bool,
+    /// PID file for --daemon and --stop (default: vanityssh.pid)
+    #[arg(long, value_name = "FILE")]
+    pub pid_file: Option<String>,
+    /// Where --daemon writes its output (default: vanityssh.log)
+    #[arg(long, value_name = "FILE")]
+    pub log_file: Option<String>,
+    /// Stop the daemon recorded in the PID file
+    #[arg(long)]
+    pub stop: bool,
+    /// Check that a private and public key belong together
+    #[arg(long, num_args = 2, value_names = ["PRIVKEY_FILE", "PUBKEY_FILE"])]
+    pub verify_pair: Option<Vec<String>>,
+    // Read by main before the rest, since its values are the defaults
+    /// Read pattern, streaming, case_sensitive, comment and threads from a
+    /// TOML file
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<String>,
+}
+
+impl Cli {
+    /// Parse command-line arguments. `args[0]` is the program name.
+    ///
+    /// `--help` and `--version` are reported as `ConfigError::HelpRequested`
+    /// and `ConfigError::VersionRequested`, so the caller decides how to show
+    /// them.
+    pub fn try_parse_args(args: &[String]) -> std::result::Result<Self, ConfigError> {
+        if args.len() < 2 {
+            return Err(ConfigError::NoArguments);
+        }
+
+        let matches = Cli::command()
+            .try_get_matches_from(args)
+            .map_err(ConfigError::from_clap)?;
+        let mut cli = Cli::from_arg_matches(&matches).map_err(ConfigError::from_clap)?;
+        // Interleave positional patterns and --pattern values as given
+        let positional = matches.indices_of("patterns").into_iter().flatten();
+        let options = matches.indices_of("pattern_options").into_iter().flatten();
+        let mut patterns: Vec<(usize, String)> = positional
+            .zip(cli.patterns.drain(..))
+            .chain(options.zip(cli.pattern_options.drain(..)))
+            .collect();
+        patterns.sort_by_key(|&(index, _)| index);
+        cli.patterns = patterns.into_iter().map(|(_, pattern)| pattern).collect();
+
+        Ok(cli)
+    }
+}
+
+/// A value parser's complaint, naming the kind of value the option takes
+#[derive(Debug)]
+pub(crate) struct Expected(pub(crate) &'static str);
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expected {}", self.0)
+    }
+}
+
+impl std::error::Error for Expected {}
+
+impl ConfigError {
+    /// Translates a clap error into the `ConfigError` it stands for
+    fn from_clap(error: clap::Error) -> Self {
+        use clap::error::{ContextKind, ContextValue, ErrorKind};
+        use std::error::Error;
+
+        let context = |kind| match error.get(kind) {
+            Some(ContextValue::String(value)) => Some(value.as_str()),
+            _ => None,
+        };
+        // clap names the option as e.g. "--threads <N>"
+        let option = context(ContextKind::InvalidArg)
+            .map(|arg| arg.split([' ', '=']).next().unwrap_or(arg).to_string());
+
+        match (error.kind(), option) {
+            (ErrorKind::DisplayHelp, _) => ConfigError::HelpRequested,
+            (ErrorKind::DisplayVersion, _) => ConfigError::VersionRequested,
+            (ErrorKind::UnknownArgument, Some(option)) => ConfigError::UnknownOption(option),
+            (ErrorKind::InvalidValue, Some(option))
+                if context(ContextKind::InvalidValue) == Some("") =>
+            {
+                ConfigError::MissingValue {
+                    option,
+                    expected: "a value",
+                }
+            }
+            (ErrorKind::ValueValidation, Some(option)) => {
+                let source = error.source();
+                match source.and_then(|source| source.downcast_ref::<Expected>()) {
+                    Some(expected) => ConfigError::InvalidValue {
+                        option,
+                        expected: expected.0,
+                    },
+                    None => ConfigError::ParseError {
+                        option,
+                        reason: source.map(ToString::to_string).unwrap_or_default(),
+                    },
+                }
+            }
+            // Anything else keeps clap's own wording, minus the usage
+            _ => {
+                let message = error.to_string();
+                let first_line = message.lines().next().unwrap_or_default();
+                ConfigError::Usage(
+                    first_line
+                        .strip_prefix("error: ")
+                        .unwrap_or(first_line)
+                        .to_string(),
+                )
+            }
+        }
+    }
+}
+
+fn positive_u64(value: &str) -> std::result::Result<u64, Expected> {
+    match value.parse() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(Expected("a positive integer")),
+    }
+}
+
+fn positive_usize(value: &str) -> std::result::Result<usize, Expected> {
+    match value.parse() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(Expected("a positive integer")),
+    }
+}
+
+fn non_negative_u64(value: &str) -> std::result::Result<u64, Expected> {
+    value
+        .parse()
+        .map_err(|_| Expected("a non-negative integer"))
+}
+
+fn positive_seconds(value: &str) -> std::result::Result<u64, Expected> {
+    match value.parse() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(Expected("a positive number of seconds")),
+    }
+}
+
+fn milliseconds(value: &str) -> std::result::Result<u64, Expected> {
+    value
+        .parse()
+        .map_err(|_| Expected("a number of milliseconds"))
+}
+
+fn positive_rate(value: &str) -> std::result::Result<f64, Expected> {
+    match value.parse::<f64>() {
+        Ok(rate) if rate > 0.0 && rate.is_finite() => Ok(rate),
+        _ => Err(Expected("a positive number")),
+    }
+}
+
 /// Configuration options for the VanitySSH program
 pub struct Config<'a> {
     pub pattern: &'a str,
+    pub extra_patterns: Vec<&'a str>,
     pub streaming: bool,
+    pub count: Option<u64>,
     pub case_sensitive: bool,
+    pub prefer_exact: bool,
+    pub near: Option<&'a str>,
     pub comment: Option<&'a str>,
     pub threads: Option<usize>,
+    pub append_authorized_keys: Option<&'a str>,
+    pub key_options: Option<&'a str>,
+    pub pub_trailing_newline: bool,
+    pub stagger_ms: Option<u64>,
+    pub ansible: bool,
+    pub cert_template: bool,
+    pub drop_when_full: bool,
+    pub prefer_fast_cores: bool,
+    pub safe: bool,
+    pub strict: bool,
+    pub algorithm: KeyAlgorithm,
+    pub json: bool,
+    /// How the final metrics are printed
+    pub output_format: OutputFormat,
+    pub quiet: bool,
+    /// How many times `-v` was given: 1 logs matches, 2 also status updates
+    pub verbose: u8,
+    pub no_progress: bool,
+    pub assess: bool,
+    pub randomart: bool,
+    pub verify: bool,
+    pub private_format: PrivateKeyFormat,
+    pub dry_run: bool,
+    pub progress_fifo: Option<&'a str>,
+    pub metrics_file: Option<&'a str>,
+    pub output_dir: Option<&'a str>,
+    pub seed_file: Option<&'a str>,
+    pub wordlist: Option<&'a str>,
+    pub prefix: Option<&'a str>,
+    pub suffix: Option<&'a str>,
+    pub seed: Option<u64>,
+    pub range: Option<(&'a str, &'a str)>,
+    pub match_target: MatchTarget,
+    pub encoding: Encoding,
+    pub match_scope: MatchScope,
+    pub match_length: Option<usize>,
+    pub max_attempts: Option<u64>,
+    pub max_rate: Option<f64>,
+    pub report_interval: Option<u64>,
+    pub timeout_secs: Option<u64>,
+    pub receipt_file: Option<&'a str>,
+    pub state_file: Option<&'a str>,
+    pub daemon: bool,
+    pub interactive: bool,
+    pub pid_file: Option<&'a str>,
+    pub log_file: Option<&'a str>,
 }
 
 impl<'a> Config<'a> {
-    /// Parse command-line arguments into a Config struct
-    pub fn parse_args(args: &'a [String]) -> Self {
-        if args.len() < 2 {
-            Self::display_help();
-            process::exit(1);
+    /// Read an options file for `--config`. Unknown keys are an error.
+    pub fn from_file(path: &Path) -> Result<OwnedConfig> {
+        let contents = fs::read_to_string(path)?;
+        let config: OwnedConfig = toml::from_str(&contents).map_err(|e| {
+            VanityError::InvalidFormat(format!("{}: {}", path.display(), e.message()))
+        })?;
+        if config.threads == Some(0) {
+            return Err(VanityError::InvalidFormat(format!(
+                "{}: threads must be a positive integer",
+                path.display()
+            )));
         }
+        Ok(config)
+    }
 
-        let mut pattern = None;
-        let mut streaming = false;
-        let mut case_sensitive = false;
-        let mut comment = None;
-        let mut threads = None;
-        let mut i = 1;
-
-        while i < args.len() {
-            match args[i].as_str() {
-                "--streaming" => {
-                    streaming = true;
-                    i += 1;
-                }
-                "--case-sensitive" => {
-                    case_sensitive = true;
-                    i += 1;
-                }
-                "--comment" => {
-                    if i + 1 < args.len() {
-                        comment = Some(args[i + 1].as_str());
-                        i += 2;
-                    } else {
-                        eprintln!("Error: --comment requires a value");
-                        Self::display_help();
-                        process::exit(1);
-                    }
-                }
-                "--threads" => {
-                    if i + 1 < args.len() {
-                        match args[i + 1].parse::<usize>() {
-                            Ok(n) if n > 0 => {
-                                threads = Some(n);
-                                i += 2;
-                            }
-                            _ => {
-                                eprintln!("Error: --threads requires a positive integer");
-                                Self::display_help();
-                                process::exit(1);
-                            }
-                        }
-                    } else {
-                        eprintln!("Error: --threads requires a value");
-                        Self::display_help();
-                        process::exit(1);
-                    }
-                }
-                "--help" => {
-                    Self::display_help();
-                    process::exit(0);
-                }
-                arg if arg.starts_with("--") => {
-                    eprintln!("Error: Unknown option: {}", arg);
-                    Self::display_help();
-                    process::exit(1);
-                }
-                _ => {
-                    if pattern.is_none() {
-                        pattern = Some(args[i].as_str());
-                    } else {
-                        eprintln!("Error: Multiple patterns specified");
-                        Self::display_help();
-                        process::exit(1);
-                    }
-                    i += 1;
-                }
-            }
+    /// Parse command-line arguments into a Config struct, starting from the
+    /// values in `file`. `args[0]` is the program name.
+    ///
+    /// Kept for compatibility: this is `Cli::try_parse_args` followed by
+    /// `Config::from_cli`. The parsed arguments are stored in `cli`, which
+    /// the returned Config borrows from; any earlier contents are replaced.
+    pub fn try_parse_args(
+        args: &[String],
+        cli: &'a mut Cli,
+        file: &'a OwnedConfig,
+    ) -> std::result::Result<Self, ConfigError> {
+        *cli = Cli::try_parse_args(args)?;
+        Self::from_cli(cli, file)
+    }
+
+    /// Like `try_parse_args`, but showing the usage and exiting on an error,
+    /// `--help` or `--version`
+    pub fn parse_args(args: &[String], cli: &'a mut Cli, file: &'a OwnedConfig) -> Self {
+        match Self::try_parse_args(args, cli, file) {
+            Ok(config) => config,
+            Err(e) => Self::exit_with_usage(e),
         }
+    }
 
-        let pattern = match pattern {
-            Some(p) => p,
-            None => {
-                eprintln!("Error: No pattern specified");
+    /// Reports an argument error, or shows the help or version it asks for,
+    /// and exits: with status 0 for `--help` and `--version`, 1 otherwise
+    pub fn exit_with_usage(error: ConfigError) -> ! {
+        match error {
+            ConfigError::HelpRequested => {
+                Self::display_help();
+                process::exit(0);
+            }
+            ConfigError::VersionRequested => {
+                Self::display_version();
+                process::exit(0);
+            }
+            ConfigError::NoArguments => {
                 Self::display_help();
                 process::exit(1);
             }
+            e => {
+                eprintln!("Error: {}", e);
+                Self::display_help();
+                process::exit(1);
+            }
+        }
+    }
+
+    /// Build a Config from parsed command-line arguments, starting from the
+    /// values in `file`.
+    ///
+    /// The first pattern on the command line replaces the file's pattern;
+    /// any more are matched as alternatives to it.
+    pub fn from_cli(cli: &'a Cli, file: &'a OwnedConfig) -> std::result::Result<Self, ConfigError> {
+        let mut cli_patterns = cli.patterns.iter().map(String::as_str);
+        let first_pattern = cli_patterns.next();
+        let extra_patterns: Vec<&str> = cli_patterns.collect();
+        let wordlist = cli.wordlist.as_deref();
+        let prefix = cli.prefix.as_deref();
+        let suffix = cli.suffix.as_deref();
+
+        // --match-fingerprint is short for --match fingerprint
+        let match_target = match (cli.match_target, cli.match_fingerprint) {
+            (Some(target), true) if target != MatchTarget::Fingerprint => {
+                return Err(ConfigError::ConflictingOptions(
+                    "--match-fingerprint can't be combined with another --match target",
+                ));
+            }
+            (Some(target), _) => target,
+            (None, true) => MatchTarget::Fingerprint,
+            (None, false) => MatchTarget::Body,
         };
 
-        Config {
-            pattern,
-            streaming,
-            case_sensitive,
-            comment,
-            threads,
+        // --json already ends with the metrics as JSON
+        if cli.json
+            && cli
+                .output_format
+                .is_some_and(|format| format != OutputFormat::Json)
+        {
+            return Err(ConfigError::ConflictingOptions(
+                "--output-format can't be combined with --json",
+            ));
         }
+
+        // A detached daemon has no terminal to type patterns into
+        if cli.interactive && cli.daemon {
+            return Err(ConfigError::ConflictingOptions(
+                "--interactive can't be combined with --daemon",
+            ));
+        }
+
+        // --prefix and --suffix build the pattern, replacing the file's
+        let pattern = if prefix.is_some() || suffix.is_some() {
+            if first_pattern.is_some() || wordlist.is_some() {
+                return Err(ConfigError::ConflictingOptions(
+                    "--prefix and --suffix can't be combined with other patterns",
+                ));
+            }
+            ""
+        } else {
+            match first_pattern.or(file.pattern.as_deref()) {
+                Some(p) => p,
+                // The wordlist supplies the patterns; main skips this placeholder
+                None if wordlist.is_some() => "",
+                None => return Err(ConfigError::NoPattern),
+            }
+        };
+
+        Ok(Config {
+            pattern,
+            extra_patterns,
+            streaming: !cli.no_streaming && (cli.streaming || file.streaming.unwrap_or(false)),
+            count: cli.count,
+            case_sensitive: !cli.no_case_sensitive
+                && (cli.case_sensitive || file.case_sensitive.unwrap_or(false)),
+            prefer_exact: cli.prefer_exact,
+            near: cli.near.as_deref(),
+            comment: cli.comment.as_deref().or(file.comment.as_deref()),
+            threads: cli.threads.or(file.threads),
+            append_authorized_keys: cli.append_authorized_keys.as_deref(),
+            key_options: cli.key_options.as_deref(),
+            pub_trailing_newline: !cli.no_pub_trailing_newline,
+            stagger_ms: cli.stagger_startup,
+            ansible: cli.ansible,
+            cert_template: cli.cert_template,
+            drop_when_full: cli.drop_when_full,
+            prefer_fast_cores: cli.prefer_fast_cores,
+            safe: cli.safe,
+            strict: cli.strict,
+            algorithm: cli.algorithm.unwrap_or_default(),
+            json: cli.json,
+            output_format: cli.output_format.unwrap_or_default(),
+            quiet: cli.quiet,
+            verbose: cli.verbose,
+            no_progress: cli.no_progress,
+            assess: cli.assess,
+            randomart: cli.randomart,
+            verify: cli.verify,
+            private_format: cli.private_format.unwrap_or_default(),
+            dry_run: cli.dry_run,
+            progress_fifo: cli.progress_fifo.as_deref(),
+            metrics_file: cli.metrics_file.as_deref(),
+            output_dir: cli.output_dir.as_deref(),
+            seed_file: cli.seed_file.as_deref(),
+            wordlist,
+            prefix,
+            suffix,
+            seed: cli.seed,
+            range: cli
+                .range
+                .as_deref()
+                .map(|range| (range[0].as_str(), range[1].as_str())),
+            match_target,
+            encoding: cli.encoding.unwrap_or_default(),
+            match_scope: cli.match_scope.unwrap_or_default(),
+            match_length: cli.match_length,
+            max_attempts: cli.max_attempts,
+            max_rate: cli.max_rate,
+            report_interval: cli.report_interval,
+            timeout_secs: cli.timeout,
+            receipt_file: cli.receipt_file.as_deref(),
+            state_file: cli.state_file.as_deref(),
+            daemon: cli.daemon,
+            interactive: cli.interactive,
+            pid_file: cli.pid_file.as_deref(),
+            log_file: cli.log_file.as_deref(),
+        })
+    }
+
+    /// Print the program name and version, for `--version`
+    pub fn display_version() {
+        print!("{}", Cli::command().render_version());
     }
 
-    /// Display usage information
+    /// Display usage information, as clap renders it from `Cli`
     pub fn display_help() {
-        println!("VanitySSH - Generate SSH keys with custom patterns");
-        println!();
-        println!("Usage: vanityssh-rust <pattern> [OPTIONS]");
-        println!("  pattern         : Regex pattern to match against the generated keys");
-        println!("  --streaming     : Continue generating keys after a match is found");
-        println!("  --comment       : Add a comment to the SSH public key");
-        println!("  --case-sensitive: Make pattern matching case-sensitive (default is case-insensitive)");
-        println!("  --threads <N>   : Number of threads to use (default: number of CPU cores)");
-        println!("  --help          : Display this help message");
+        let _ = Cli::command().print_help();
     }
 }
@@ -0,0 +1,105 @@
+// src/cores.rs
+use crate::keygen;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Thread counts above this many times the CPU count get a warning
+pub const OVERSUBSCRIBE_FACTOR: usize = 4;
+
+/// Largest thread count used; larger requests are clamped to it
+pub const MAX_THREADS: usize = 4096;
+
+/// Why `effective_thread_count` second-guessed a requested thread count
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadWarning {
+    /// More than `OVERSUBSCRIBE_FACTOR` threads per CPU, which only adds
+    /// context switching; the count is used as is
+    Oversubscribed,
+    /// More than `MAX_THREADS`, so `MAX_THREADS` is used instead
+    Clamped,
+}
+
+/// Decides how many worker threads to run for a request of `requested` on a
+/// machine with `cpu_count` CPUs, and whether to warn about it
+pub fn effective_thread_count(
+    requested: usize,
+    cpu_count: usize,
+) -> (usize, Option<ThreadWarning>) {
+    if requested > MAX_THREADS {
+        (MAX_THREADS, Some(ThreadWarning::Clamped))
+    } else if requested > cpu_count.max(1).saturating_mul(OVERSUBSCRIBE_FACTOR) {
+        (requested, Some(ThreadWarning::Oversubscribed))
+    } else {
+        (requested, None)
+    }
+}
+
+/// Measured key generation rate of a single CPU core
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoreRate {
+    pub core: usize,
+    pub keys_per_second: f64,
+}
+
+/// Returns the ids of the cores worker threads can be pinned to, or an empty
+/// list if the platform doesn't support pinning
+pub fn available_cores() -> Vec<usize> {
+    core_affinity::get_core_ids()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|core| core.id)
+        .collect()
+}
+
+/// Pins the calling thread to `core`. Returns false if pinning failed.
+pub fn pin_current_thread(core: usize) -> bool {
+    core_affinity::set_for_current(core_affinity::CoreId { id: core })
+}
+
+/// Generates keys on every available core at once for `duration` and
+/// returns each core's rate, in core order.
+///
+/// Running the cores together measures them under the same load the search
+/// puts on them, which is what matters on CPUs that clock down when busy.
+pub fn benchmark_cores(duration: Duration) -> Vec<CoreRate> {
+    let workers: Vec<_> = available_cores()
+        .into_iter()
+        .map(|core| {
+            thread::spawn(move || {
+                let pinned = pin_current_thread(core);
+                let start = Instant::now();
+                let mut keys: u64 = 0;
+                while start.elapsed() < duration {
+                    if keygen::generate_openssh_key_pair(None).is_ok() {
+                        keys += 1;
+                    }
+                }
+                let keys_per_second = keys as f64 / start.elapsed().as_secs_f64();
+                pinned.then_some(CoreRate {
+                    core,
+                    keys_per_second,
+                })
+            })
+        })
+        .collect();
+
+    workers
+        .into_iter()
+        .filter_map(|worker| worker.join().ok().flatten())
+        .collect()
+}
+
+/// Orders cores from fastest to slowest measured rate
+pub fn rank_cores(mut rates: Vec<CoreRate>) -> Vec<CoreRate> {
+    rates.sort_by(|a, b| b.keys_per_second.total_cmp(&a.keys_per_second));
+    rates
+}
+
+/// Benchmarks the cores and returns the ids of the fastest `count`
+pub fn fastest_cores(count: usize, duration: Duration) -> Vec<usize> {
+    rank_cores(benchmark_cores(duration))
+        .into_iter()
+        .take(count)
+        .map(|rate| rate.core)
+        .collect()
+}
@@ -0,0 +1,143 @@
+// src/daemon.rs
+
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::signals;
+
+/// Default PID file used by `--daemon` and `--stop`
+pub const DEFAULT_PID_FILE: &str = "vanityssh.pid";
+
+/// Default log file used by `--daemon`
+pub const DEFAULT_LOG_FILE: &str = "vanityssh.log";
+
+/// Detaches the process from the terminal with the classic double fork.
+///
+/// The original process exits once the daemon has been forked. The daemon
+/// keeps the current directory (so relative paths keep working), writes its
+/// PID to `pid_file` and sends stdout and stderr to `log_file`.
+///
+/// Must be called before any threads are spawned.
+pub fn daemonize(pid_file: &Path, log_file: &Path) -> io::Result<()> {
+    if let Some(pid) = running_pid(pid_file) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("daemon already running with PID {}", pid),
+        ));
+    }
+
+    // Open files before forking so errors still reach the terminal
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)?;
+    let dev_null = File::open("/dev/null")?;
+
+    // SAFETY: the process is still single-threaded, so forking is sound
+    unsafe {
+        match libc::fork() {
+            -1 => return Err(io::Error::last_os_error()),
+            0 => {}
+            child => {
+                // Wait for the intermediate child, which exits right away
+                libc::waitpid(child, std::ptr::null_mut(), 0);
+                println!(
+                    "Daemon started (PID file: {}, log: {})",
+                    pid_file.display(),
+                    log_file.display()
+                );
+                std::process::exit(0);
+            }
+        }
+
+        // Become session leader so we lose the controlling terminal
+        if libc::setsid() == -1 {
+            libc::_exit(1);
+        }
+
+        // Fork again so the daemon can never reacquire a terminal
+        match libc::fork() {
+            -1 => libc::_exit(1),
+            0 => {}
+            _ => libc::_exit(0),
+        }
+
+        libc::dup2(dev_null.as_raw_fd(), libc::STDIN_FILENO);
+        libc::dup2(log.as_raw_fd(), libc::STDOUT_FILENO);
+        libc::dup2(log.as_raw_fd(), libc::STDERR_FILENO);
+    }
+
+    fs::write(pid_file, format!("{}\n", std::process::id()))?;
+    Ok(())
+}
+
+/// Starts a thread that waits for SIGTERM, removes the PID file and exits.
+/// SIGTERM must already be blocked with `signals::block`.
+pub fn spawn_stop_watcher(pid_file: &Path) {
+    let pid_file: PathBuf = pid_file.to_path_buf();
+    let set = signals::signal_set(&[libc::SIGTERM]);
+
+    thread::spawn(move || {
+        let mut signal = 0;
+        // SAFETY: `set` is a valid, initialised signal set
+        unsafe {
+            libc::sigwait(&set, &mut signal);
+        }
+        println!("Received SIGTERM, stopping");
+        let _ = fs::remove_file(&pid_file);
+        std::process::exit(0);
+    });
+}
+
+/// Stops a running daemon by sending SIGTERM to the PID in `pid_file` and
+/// waiting for it to remove the file.
+pub fn stop(pid_file: &Path) -> io::Result<u32> {
+    let pid = read_pid(pid_file)?;
+
+    // SAFETY: kill has no memory-safety preconditions
+    if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } == -1 {
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() == Some(libc::ESRCH) {
+            // Stale PID file from a daemon that is already gone
+            fs::remove_file(pid_file)?;
+            return Ok(pid);
+        }
+        return Err(err);
+    }
+
+    let start = Instant::now();
+    while pid_file.exists() {
+        if start.elapsed() > Duration::from_secs(10) {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("daemon with PID {} did not stop", pid),
+            ));
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    Ok(pid)
+}
+
+/// Helper function to read a PID from a PID file
+fn read_pid(pid_file: &Path) -> io::Result<u32> {
+    fs::read_to_string(pid_file)?
+        .trim()
+        .parse()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid PID file"))
+}
+
+/// Helper function returning the PID in `pid_file` if that process is alive
+fn running_pid(pid_file: &Path) -> Option<u32> {
+    let pid = read_pid(pid_file).ok()?;
+    // SAFETY: signal 0 only checks that the process exists
+    if unsafe { libc::kill(pid as libc::pid_t, 0) } == 0 {
+        Some(pid)
+    } else {
+        None
+    }
+}
@@ -25,7 +25,65 @@ pub enum VanityError {
     #[error("Invalid format: {0}")]
     InvalidFormat(String),
 
+    /// Error when a key file cannot be read
+    #[error("Key file error: {0}")]
+    KeyFileError(String),
+
+    /// Error when a private key does not correspond to a public key
+    #[error("Key mismatch: {0}")]
+    KeyMismatch(String),
+
     /// I/O error
     #[error("I/O error: {0}")]
     IoError(#[from] std::io::Error),
 }
+
+/// Error from parsing the command-line arguments
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// No arguments were given at all
+    #[error("No arguments given")]
+    NoArguments,
+
+    /// `--help` was given
+    #[error("Help requested")]
+    HelpRequested,
+
+    /// `--version` was given
+    #[error("Version requested")]
+    VersionRequested,
+
+    /// An argument starting with `-` that isn't an option
+    #[error("Unknown option: {0}")]
+    UnknownOption(String),
+
+    /// An option was the last argument but needs a value
+    #[error("{option} requires {expected}")]
+    MissingValue {
+        option: String,
+        expected: &'static str,
+    },
+
+    /// An option's value isn't the kind of value it takes
+    #[error("{option} requires {expected}")]
+    InvalidValue {
+        option: String,
+        expected: &'static str,
+    },
+
+    /// An option's value was rejected by its parser
+    #[error("{option}: {reason}")]
+    ParseError { option: String, reason: String },
+
+    /// Any other misuse of the command line, in the argument parser's words
+    #[error("{0}")]
+    Usage(String),
+
+    /// Options that can't be used together
+    #[error("{0}")]
+    ConflictingOptions(&'static str),
+
+    /// Neither a pattern nor anything that stands in for one was given
+    #[error("No pattern specified")]
+    NoPattern,
+}
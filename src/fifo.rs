@@ -0,0 +1,89 @@
+// src/fifo.rs
+use crate::error::{Result, VanityError};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Writes progress events to a named pipe for external monitors.
+///
+/// Writes never block: while nobody has the FIFO open for reading, or while
+/// the reader falls behind and the pipe is full, events are dropped.
+pub struct ProgressFifo {
+    path: PathBuf,
+    file: Option<File>,
+}
+
+impl ProgressFifo {
+    /// Opens the FIFO at `path`, creating it if it doesn't exist.
+    /// Fails if `path` exists but isn't a FIFO.
+    #[cfg(unix)]
+    pub fn create(path: &Path) -> Result<Self> {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::fs::FileTypeExt;
+
+        if !path.exists() {
+            let c_path = CString::new(path.as_os_str().as_bytes())
+                .map_err(|e| VanityError::InvalidFormat(e.to_string()))?;
+            if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+        } else if !path.metadata()?.file_type().is_fifo() {
+            return Err(VanityError::InvalidFormat(format!(
+                "{} exists and is not a FIFO",
+                path.display()
+            )));
+        }
+
+        Ok(ProgressFifo {
+            path: path.to_path_buf(),
+            file: None,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn create(_path: &Path) -> Result<Self> {
+        Err(VanityError::InvalidFormat(
+            "progress FIFOs are only supported on Unix".to_string(),
+        ))
+    }
+
+    /// Writes one line, dropping it if it can't be written right away
+    pub fn send(&mut self, line: &str) {
+        if self.file.is_none() {
+            self.file = self.open_writer();
+        }
+        let Some(ref mut file) = self.file else {
+            return;
+        };
+
+        // Lines shorter than PIPE_BUF are written atomically or not at all
+        let mut buf = String::with_capacity(line.len() + 1);
+        buf.push_str(line);
+        buf.push('\n');
+        match file.write(buf.as_bytes()) {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            // The reader went away; reopen once a new one shows up
+            Err(_) => self.file = None,
+        }
+    }
+
+    /// Opens the write end without blocking, which fails while there is no
+    /// reader
+    #[cfg(unix)]
+    fn open_writer(&self) -> Option<File> {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        std::fs::OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&self.path)
+            .ok()
+    }
+
+    #[cfg(not(unix))]
+    fn open_writer(&self) -> Option<File> {
+        None
+    }
+}
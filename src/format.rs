@@ -0,0 +1,49 @@
+// src/format.rs
+
+use crate::error::{Result, VanityError};
+use std::path::Path;
+
+/// Checks that a key comment can be embedded in a double-quoted YAML scalar
+/// without changing the meaning of the document.
+///
+/// Control characters (including newlines and tabs) are rejected, as are
+/// `"` and `\`, which would otherwise need escaping that some YAML consumers
+/// handle inconsistently.
+pub fn validate_yaml_comment(comment: &str) -> Result<()> {
+    if let Some(c) = comment
+        .chars()
+        .find(|c| c.is_control() || *c == '"' || *c == '\\')
+    {
+        return Err(VanityError::InvalidFormat(format!(
+            "Comment contains a character that is unsafe in YAML: {:?}",
+            c
+        )));
+    }
+    Ok(())
+}
+
+/// Formats a public key as an Ansible `authorized_key` task.
+///
+/// The key is emitted as a double-quoted scalar so that the spaces and
+/// base64 characters (`+`, `/`, `=`) are never interpreted by YAML.
+pub fn ansible_authorized_key(public_key: &str) -> Result<String> {
+    validate_yaml_comment(public_key)?;
+
+    Ok(format!(
+        "- name: Install vanity SSH key\n  ansible.posix.authorized_key:\n    user: \"{{{{ ansible_user }}}}\"\n    state: present\n    key: \"{}\"",
+        public_key
+    ))
+}
+
+/// Formats a stub `ssh-keygen` command that signs a written public key into
+/// an SSH certificate. `ca_key`, `<id>` and `<principals>` are placeholders
+/// for the user to fill in.
+pub fn cert_template(public_key_path: &Path) -> String {
+    let path = public_key_path.display().to_string();
+    let path = if path.contains(char::is_whitespace) {
+        format!("'{}'", path.replace('\'', "'\\''"))
+    } else {
+        path
+    };
+    format!("ssh-keygen -s ca_key -I <id> -n <principals> {}", path)
+}
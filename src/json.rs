@@ -0,0 +1,89 @@
+// src/json.rs
+use crate::error::{Result, VanityError};
+use crate::thread_pool::KeyMatch;
+use crate::PerformanceMetrics;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Serializable view of a match, emitted as one line per match by `--json`
+#[derive(Serialize)]
+pub struct MatchRecord<'a> {
+    pub public_key: &'a str,
+    pub private_key: &'a str,
+    pub attempts: u64,
+    pub total_attempts: u64,
+    pub thread_id: usize,
+    pub timestamp: &'a str,
+    pub vanity_score: f64,
+    pub matched_pattern: &'a str,
+    pub exact_case: bool,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub captures: BTreeMap<&'a str, &'a str>,
+}
+
+impl<'a> MatchRecord<'a> {
+    pub fn new(key_match: &'a KeyMatch, timestamp: &'a str, vanity_score: f64) -> Self {
+        MatchRecord {
+            public_key: &key_match.public_key,
+            private_key: &key_match.private_key,
+            attempts: key_match.attempts,
+            total_attempts: key_match.total_attempts,
+            thread_id: key_match.thread_id,
+            timestamp,
+            vanity_score,
+            matched_pattern: &key_match.matched_pattern,
+            exact_case: key_match.exact_case,
+            captures: key_match
+                .captures
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str()))
+                .collect(),
+        }
+    }
+}
+
+/// Serializable view of the final metrics, emitted last by `--json`
+#[derive(Serialize)]
+pub struct MetricsRecord {
+    pub attempts: u64,
+    pub matches_found: u64,
+    pub duration_secs: f64,
+    pub keys_per_second: f64,
+    pub per_thread: Vec<u64>,
+    pub near_misses: u64,
+}
+
+impl From<&PerformanceMetrics> for MetricsRecord {
+    fn from(metrics: &PerformanceMetrics) -> Self {
+        MetricsRecord {
+            attempts: metrics.attempts,
+            matches_found: metrics.matches_found,
+            duration_secs: metrics.duration.as_secs_f64(),
+            keys_per_second: metrics.keys_per_second,
+            per_thread: metrics.per_thread.clone(),
+            near_misses: metrics.near_misses,
+        }
+    }
+}
+
+/// Serializes `value` as a single line of JSON
+pub fn to_line<T: Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string(value).map_err(|e| VanityError::EncodingError(e.to_string()))
+}
+
+/// Progress event written to `--progress-fifo` on every display refresh
+#[derive(Serialize)]
+pub struct ProgressEvent {
+    pub event: &'static str,
+    #[serde(flatten)]
+    pub metrics: MetricsRecord,
+}
+
+impl From<&PerformanceMetrics> for ProgressEvent {
+    fn from(metrics: &PerformanceMetrics) -> Self {
+        ProgressEvent {
+            event: "progress",
+            metrics: MetricsRecord::from(metrics),
+        }
+    }
+}
@@ -1,26 +1,66 @@
 // src/lib.rs
 // Updated: 2025-04-22 15:50:00 by kengggg
 
+#[cfg(feature = "cli")]
+pub mod config;
+pub mod cores;
 pub mod error;
+pub mod fifo;
+pub mod format;
+pub mod json;
 pub mod keygen;
 pub mod matcher;
+pub mod output;
+pub mod prometheus;
+pub mod quality;
+pub mod receipt;
+/// The printing search behind the command-line tool, with its progress
+/// display
+#[cfg(feature = "cli")]
+pub mod report;
+pub mod search;
 pub mod ssh;
+pub mod state;
+pub mod stats;
 pub mod thread_pool;
-
-use crate::error::Result;
-use crate::thread_pool::{run_thread_pool, ThreadPoolConfig};
-use chrono::Local;
-use crossbeam_channel::select;
-use indicatif::{ProgressBar, ProgressStyle};
+pub mod verify;
+
+#[cfg(feature = "cli")]
+pub use report::{
+    run_search, stream_keys_and_match, stream_openssh_keys_and_match,
+    stream_openssh_keys_and_match_mt, OutputOptions,
+};
+pub use search::{stream_with_handlers, VanitySearch};
+
+use crate::error::{Result, VanityError};
+use crate::thread_pool::{run_thread_pool, KeyMatch, MatchCriteria, ThreadPoolConfig};
+use rand::{CryptoRng, RngCore};
+use std::collections::VecDeque;
 use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
 use std::time::{Duration, Instant};
 
+/// How far back `PerformanceMetrics::instantaneous_rate` looks
+const RATE_WINDOW: Duration = Duration::from_secs(5);
+
+/// Most samples kept for the rolling rate
+const MAX_RATE_SAMPLES: usize = 64;
+
 /// Performance metrics for key generation
 pub struct PerformanceMetrics {
     pub attempts: u64,
     pub matches_found: u64,
     pub duration: Duration,
+    /// Average over the whole run
     pub keys_per_second: f64,
+    /// Keys generated by each worker thread, indexed by thread id
+    pub per_thread: Vec<u64>,
+    /// Keys that matched the near pattern but not the search's patterns;
+    /// 0 unless `ThreadPoolConfig::near_pattern` is set
+    pub near_misses: u64,
+    /// Recent `(duration, attempts)` updates, oldest first
+    samples: VecDeque<(Duration, u64)>,
 }
 
 impl Default for PerformanceMetrics {
@@ -42,6 +82,34 @@ impl fmt::Display for PerformanceMetrics {
     }
 }
 
+/// How `PerformanceMetrics::render` writes the metrics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// The one-line `Display` summary
+    #[default]
+    Text,
+    /// The line of JSON that `--json` ends with
+    Json,
+    /// A header line and a line of values, for spreadsheets
+    Csv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = VanityError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(VanityError::InvalidFormat(format!(
+                "unknown output format '{}', expected text, json or csv",
+                s
+            ))),
+        }
+    }
+}
+
 impl PerformanceMetrics {
     /// Creates a new metrics instance
     pub fn new() -> Self {
@@ -50,6 +118,9 @@ impl PerformanceMetrics {
             matches_found: 0,
             duration: Duration::from_secs(0),
             keys_per_second: 0.0,
+            per_thread: Vec::new(),
+            near_misses: 0,
+            samples: VecDeque::new(),
         }
     }
 
@@ -64,190 +135,236 @@ impl PerformanceMetrics {
         if seconds > 0.0 {
             self.keys_per_second = attempts as f64 / seconds;
         }
+
+        // Start over if the caller went back in time
+        if self
+            .samples
+            .back()
+            .is_some_and(|&(last, _)| last > duration)
+        {
+            self.samples.clear();
+        }
+        self.samples.push_back((duration, attempts));
+
+        // Keep one sample from before the window so the rate spans all of it
+        let window_start = duration.saturating_sub(RATE_WINDOW);
+        while self.samples.len() > MAX_RATE_SAMPLES
+            || self
+                .samples
+                .get(1)
+                .is_some_and(|&(time, _)| time <= window_start)
+        {
+            self.samples.pop_front();
+        }
     }
-}
 
-/// Continuously generates random ed25519 key pairs in OpenSSH format
-/// and matches the public key against a regex pattern.
-/// This is the multi-threaded version of the key generation function.
-///
-/// # Arguments
-///
-/// * `pattern` - The regex pattern to match against
-/// * `streaming` - Whether to continue after finding a match
-/// * `comment` - Optional comment to add to the SSH key
-/// * `case_sensitive` - Whether to perform case-sensitive matching
-/// * `threads` - Number of worker threads to use (default: number of CPU cores)
-///
-/// # Returns
-///
-/// Performance metrics for the operation
-pub fn stream_openssh_keys_and_match_mt(
-    pattern: &str,
-    streaming: bool,
-    comment: Option<&str>,
-    case_sensitive: bool,
-    threads: Option<usize>,
-) -> Result<PerformanceMetrics> {
-    // Determine thread count: use provided value or CPU count
-    let thread_count = threads.unwrap_or_else(num_cpus::get);
-
-    // Setup progress bar
-    let mut pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} {msg}")
-            .unwrap(),
-    );
-
-    // Performance tracking
-    let start_time = Instant::now();
-    let mut last_update = Instant::now();
-    let update_interval = Duration::from_millis(500);
-
-    // Create thread pool configuration
-    let config = ThreadPoolConfig {
-        pattern: pattern.to_string(),
-        thread_count,
-        case_sensitive,
-        streaming,
-        comment: comment.map(|s| s.to_string()),
-    };
+    /// Adds `attempts` to the count for worker `thread_id`
+    pub fn record_thread_attempts(&mut self, thread_id: usize, attempts: u64) {
+        if self.per_thread.len() <= thread_id {
+            self.per_thread.resize(thread_id + 1, 0);
+        }
+        self.per_thread[thread_id] += attempts;
+    }
 
-    // Start the thread pool
-    let (match_receiver, status_receiver) = run_thread_pool(config)?;
+    /// Per-thread counts like `thread 0: 1.2M, thread 1: 300`, for spotting
+    /// workers that are starved or have died
+    pub fn per_thread_breakdown(&self) -> String {
+        self.per_thread
+            .iter()
+            .enumerate()
+            .map(|(thread_id, &attempts)| {
+                format!(
+                    "thread {}: {}",
+                    thread_id,
+                    stats::format_count(attempts as f64)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
 
-    // Track attempts and matches
-    let mut total_attempts: u64 = 0;
-    let mut matches_found: u64 = 0;
+    /// The metrics written out in `format`, without a trailing newline
+    pub fn render(&self, format: OutputFormat) -> String {
+        match format {
+            OutputFormat::Text => self.to_string(),
+            OutputFormat::Json => json::to_line(&json::MetricsRecord::from(self))
+                .expect("metrics are plain numbers, which always serialize"),
+            OutputFormat::Csv => format!(
+                "attempts,matches,duration_s,keys_per_sec\n{},{},{:.3},{:.2}",
+                self.attempts,
+                self.matches_found,
+                self.duration.as_secs_f64(),
+                self.keys_per_second
+            ),
+        }
+    }
 
-    // Performance metrics to return
-    let mut metrics = PerformanceMetrics::new();
+    /// Expected time to find `n` matches at `keys_per_second` when each key
+    /// matches with probability `per_key_probability`.
+    ///
+    /// The keys needed for one match follow a geometric distribution with
+    /// mean `1 / per_key_probability`, so `n` matches take `n` times that on
+    /// average. Returns `Duration::MAX` if the probability or the rate isn't
+    /// positive, or the result doesn't fit in a `Duration`.
+    pub fn eta_for_matches(n: u64, per_key_probability: f64, keys_per_second: f64) -> Duration {
+        if n == 0 {
+            return Duration::ZERO;
+        }
+        if !(per_key_probability > 0.0 && keys_per_second > 0.0) {
+            return Duration::MAX;
+        }
+        let expected_keys = n as f64 / per_key_probability.min(1.0);
+        Duration::try_from_secs_f64(expected_keys / keys_per_second).unwrap_or(Duration::MAX)
+    }
 
-    // Enable steady spinner tick
-    pb.enable_steady_tick(Duration::from_millis(100));
-
-    loop {
-        // Use crossbeam's select! to handle multiple channels
-        select! {
-            // Handle key matches
-            recv(match_receiver) -> msg => {
-                if let Ok(key_match) = msg {
-                    // Update counters with the match information
-                    total_attempts += key_match.attempts;
-                    matches_found += 1;
-
-                    let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
-                    let elapsed = start_time.elapsed();
-
-                    // Update metrics
-                    metrics.update(total_attempts, matches_found, elapsed);
-
-                    // Clear progress spinner when reporting a match
-                    pb.finish_and_clear();
-
-                    // Report the match
-                    println!(
-                        "\n[{}] Match found after {} attempts by thread {}!",
-                        timestamp, key_match.attempts, key_match.thread_id
-                    );
-                    println!("Public Key:  {}", key_match.public_key);
-                    println!("Private Key:\n{}", key_match.private_key);
-                    println!("Performance: {}", metrics);
-
-                    // If not in streaming mode, exit
-                    if !streaming {
-                        return Ok(metrics);
-                    }
-
-                    // In streaming mode, we need to completely recreate the progress bar
-                    // rather than just reinitializing it
-                    pb = ProgressBar::new_spinner();
-                    pb.set_style(
-                        ProgressStyle::default_spinner()
-                            .template("{spinner:.green} {msg}")
-                            .unwrap(),
-                    );
-                    pb.enable_steady_tick(Duration::from_millis(100));
-
-                    // Reset update timer to ensure immediate refresh
-                    last_update = Instant::now().checked_sub(update_interval * 2).unwrap_or(Instant::now());
-
-                    // Force an immediate update of the progress display with a clear message
-                    // that indicates we're continuing the search
-                    let now = Instant::now();
-                    let elapsed = now.duration_since(start_time);
-                    metrics.update(total_attempts, matches_found, elapsed);
-
-                    // Add a newline before continuing to ensure progress bar appears on its own line
-                    println!("\nContinuing search for more matches...");
-
-                    pb.set_message(format!("Attempts: {} | Matches: {} | Duration: {:.2}s | Speed: {:.2} keys/sec (Threads: {})",
-                        total_attempts, matches_found, elapsed.as_secs_f64(), metrics.keys_per_second, thread_count));
-                } else {
-                    // Channel closed, exit
-                    break;
-                }
-            },
-
-            // Handle status updates
-            recv(status_receiver) -> msg => {
-                if let Ok(status) = msg {
-                    // Update attempt counter
-                    total_attempts += status.attempts;
-
-                    // Refresh display if update interval has passed
-                    let now = Instant::now();
-                    if now.duration_since(last_update) >= update_interval {
-                        let elapsed = now.duration_since(start_time);
-                        metrics.update(total_attempts, matches_found, elapsed);
-                        pb.set_message(format!("Attempts: {} | Matches: {} | Duration: {:.2}s | Speed: {:.2} keys/sec (Threads: {})",
-                            total_attempts, matches_found, elapsed.as_secs_f64(), metrics.keys_per_second, thread_count));
-                        last_update = now;
-                    }
-                }
-            },
-
-            // Handle timeout to update display even if no status updates received
-            default(update_interval) => {
-                let now = Instant::now();
-                let elapsed = now.duration_since(start_time);
-                metrics.update(total_attempts, matches_found, elapsed);
-                pb.set_message(format!("Attempts: {} | Matches: {} | Duration: {:.2}s | Speed: {:.2} keys/sec (Threads: {})",
-                    total_attempts, matches_found, elapsed.as_secs_f64(), metrics.keys_per_second, thread_count));
-                last_update = now;
+    /// Keys per second over roughly the last few seconds of updates, which
+    /// shows slowdowns the lifetime average hides. Falls back to the
+    /// lifetime average until there are two updates to compare.
+    pub fn instantaneous_rate(&self) -> f64 {
+        match (self.samples.front(), self.samples.back()) {
+            (Some(&(start, start_attempts)), Some(&(end, end_attempts))) if end > start => {
+                end_attempts.saturating_sub(start_attempts) as f64 / (end - start).as_secs_f64()
             }
+            _ => self.keys_per_second,
         }
     }
+}
 
-    pb.finish_and_clear();
+/// Measures elapsed time while leaving out periods the process was suspended.
+///
+/// When a process is stopped (Ctrl-Z / SIGSTOP) and later resumed, the wall
+/// clock keeps running, which would make keys/sec look far lower than it is.
+/// The search loop samples this clock at least every update interval, so a
+/// gap between two samples much longer than that means the process wasn't
+/// running, and the gap is not counted as active time.
+pub struct ActiveClock {
+    last_sample: Instant,
+    active: Duration,
+    max_gap: Duration,
+}
 
-    // Final update to metrics
-    let elapsed = start_time.elapsed();
-    metrics.update(total_attempts, matches_found, elapsed);
+impl ActiveClock {
+    /// Starts a clock at `start`; gaps longer than `max_gap` are excluded
+    pub fn new(start: Instant, max_gap: Duration) -> Self {
+        ActiveClock {
+            last_sample: start,
+            active: Duration::from_secs(0),
+            max_gap,
+        }
+    }
 
-    Ok(metrics)
+    /// Records a sample at `now` and returns the active time so far
+    pub fn sample(&mut self, now: Instant) -> Duration {
+        let gap = now.saturating_duration_since(self.last_sample);
+        if gap <= self.max_gap {
+            self.active += gap;
+        }
+        self.last_sample = now;
+        self.active
+    }
+
+    /// Returns the active time recorded up to the last sample
+    pub fn active(&self) -> Duration {
+        self.active
+    }
 }
 
-// Keep the original single-threaded function for backward compatibility
-pub fn stream_openssh_keys_and_match(
-    pattern: &str,
-    streaming: bool,
-    comment: Option<&str>,
-    case_sensitive: bool,
-) -> Result<PerformanceMetrics> {
-    // By default, use the multi-threaded version with 1 thread
-    stream_openssh_keys_and_match_mt(pattern, streaming, comment, case_sensitive, Some(1))
+/// Expected number of attempts until the next match, for the progress bar.
+///
+/// Only known for a single pattern matched against the base64 body of an
+/// Ed25519 key, where `matcher::estimate_difficulty` applies.
+pub fn estimated_attempts(config: &ThreadPoolConfig) -> Option<u64> {
+    let estimable = config.algorithm == keygen::KeyAlgorithm::Ed25519
+        && config.extra_patterns.is_empty()
+        && config.match_target == matcher::MatchTarget::Body
+        && config.encoding == matcher::Encoding::Base64
+        && config.match_scope == matcher::MatchScope::Body
+        && config.match_length.is_none()
+        && config.range.is_none();
+    if !estimable {
+        return None;
+    }
+
+    matcher::estimate_difficulty(&config.pattern, config.case_sensitive)
+        .filter(|difficulty| difficulty.is_possible())
+        .map(|difficulty| difficulty.expected_attempts.ceil().max(1.0) as u64)
 }
 
-// Original stream_keys_and_match for backward compatibility
-pub fn stream_keys_and_match(
-    pattern: &str,
-    streaming: bool,
-    case_sensitive: bool,
-) -> Result<PerformanceMetrics> {
-    // Call the multi-threaded version with 1 thread
-    stream_openssh_keys_and_match_mt(pattern, streaming, None, case_sensitive, Some(1))
+/// Runs the thread pool until `n` matching keys have been found and returns them.
+///
+/// Nothing is printed or written to disk. The worker threads are shut down
+/// and joined before this function returns. The `streaming` field of the
+/// config is ignored, since collecting more than one key requires it.
+pub fn collect_vanity_keys(mut config: ThreadPoolConfig, n: usize) -> Result<Vec<KeyMatch>> {
+    if n == 0 {
+        return Ok(Vec::new());
+    }
+
+    config.streaming = true;
+    let handle = run_thread_pool(config)?;
+
+    let mut keys = Vec::with_capacity(n);
+    while keys.len() < n {
+        match handle.match_receiver.recv() {
+            Ok(key_match) => keys.push(key_match),
+            Err(_) => {
+                handle.shutdown();
+                return Err(VanityError::KeyGenerationError(
+                    "worker threads exited before enough keys were found".into(),
+                ));
+            }
+        }
+    }
+
+    handle.shutdown();
+    Ok(keys)
+}
+
+/// Searches for one matching key on the calling thread, without spawning
+/// threads or creating channels.
+///
+/// A seeded search uses the same RNG as worker 0 of a pool, so it finds the
+/// same key as a seeded single-threaded pool. `thread_count`, `streaming`,
+/// `startup_stagger`, `pin_cores` and `drop_when_full` are ignored. Returns
+/// `None` if `max_attempts` is reached or the terminate flag is set first.
+pub fn find_key_inline(config: &ThreadPoolConfig) -> Result<Option<KeyMatch>> {
+    let mut rng = match config.seed {
+        Some(ref seed) => keygen::KeyRng::seeded(keygen::thread_seed(seed, 0)),
+        None => keygen::KeyRng::os_seeded(),
+    };
+    find_key_inline_with(config, &mut rng)
+}
+
+/// Like `find_key_inline`, but draws keys from `rng`
+pub fn find_key_inline_with<R: RngCore + CryptoRng>(
+    config: &ThreadPoolConfig,
+    rng: &mut R,
+) -> Result<Option<KeyMatch>> {
+    // Fails up front on an invalid pattern
+    let criteria = MatchCriteria::from_config(config)?;
+    let mut body = String::new();
+
+    let mut attempts: u64 = 0;
+    while config.max_attempts.is_none_or(|max| attempts < max) {
+        if let Some(ref terminate) = config.terminate {
+            if terminate.load(Ordering::Relaxed) {
+                break;
+            }
+        }
+
+        attempts += 1;
+        let found =
+            criteria.try_key(config.algorithm, rng, config.comment.as_deref(), &mut body)?;
+        if let Some(key_match) = found {
+            return Ok(Some(KeyMatch {
+                attempts,
+                total_attempts: attempts,
+                ..key_match
+            }));
+        }
+    }
+
+    Ok(None)
 }
 
 // For test helper function
@@ -1,12 +1,29 @@
 // src/main.rs
 use std::env;
-use vanityssh_rust::{error::Result, stream_openssh_keys_and_match_mt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use vanityssh_rust::config::{Cli, Config, OwnedConfig};
+use vanityssh_rust::keygen::KeyAlgorithm;
+use vanityssh_rust::matcher::{self, Encoding, MatchScope, MatchTarget};
+use vanityssh_rust::receipt::SeedSource;
+use vanityssh_rust::ssh::PrivateKeyFormat;
+use vanityssh_rust::thread_pool::ThreadPoolConfig;
+use vanityssh_rust::{
+    cores, error::Result, format, json, keygen, run_search, ssh, verify, OutputFormat,
+    OutputOptions,
+};
 
-mod config;
+#[cfg(unix)]
+mod daemon;
+#[cfg(unix)]
+mod signals;
 mod validation;
 
-use config::Config;
-use validation::{display_thread_info, validate_pattern, validate_threads};
+use validation::{
+    display_estimate, display_thread_info, validate_pattern, validate_satisfiable, validate_threads,
+};
 
 /// Entry point for the VanitySSH key generation application
 ///
@@ -22,41 +39,464 @@ use validation::{display_thread_info, validate_pattern, validate_threads};
 fn main() -> Result<()> {
     // Parse arguments
     let args: Vec<String> = env::args().collect();
+    let cli = match Cli::try_parse_args(&args) {
+        Ok(cli) => cli,
+        Err(e) => Config::exit_with_usage(e),
+    };
 
-    // Display help if requested directly
-    if args.len() > 1 && args[1] == "--help" {
-        Config::display_help();
-        std::process::exit(0);
+    // Verifying an existing key pair doesn't need a pattern
+    if let Some(files) = cli.verify_pair.as_deref() {
+        let private_path = Path::new(&files[0]);
+        let public_path = Path::new(&files[1]);
+        return match verify::verify_key_pair_files(private_path, public_path) {
+            Ok(()) => {
+                println!(
+                    "OK: {} matches {}",
+                    private_path.display(),
+                    public_path.display()
+                );
+                Ok(())
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                Err(e)
+            }
+        };
     }
 
-    let config = Config::parse_args(&args);
+    // Stopping a daemon doesn't need a pattern either
+    if cli.stop {
+        return stop_daemon(cli.pid_file.as_deref());
+    }
+
+    // Options from a --config file are defaults for the command line
+    let file_config = match cli.config.as_deref() {
+        Some(path) => match Config::from_file(Path::new(path)) {
+            Ok(file_config) => file_config,
+            Err(e) => {
+                eprintln!("Error: could not load config file: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => OwnedConfig::default(),
+    };
+
+    let config = match Config::from_cli(&cli, &file_config) {
+        Ok(config) => config,
+        Err(e) => Config::exit_with_usage(e),
+    };
+    init_logger(config.verbose);
+
+    // A --wordlist adds its patterns to any given on the command line
+    let mut patterns: Vec<String> = Vec::new();
+    if config.prefix.is_some() || config.suffix.is_some() {
+        // The fixed Ed25519 header comes before the part the user picks
+        let header = if config.algorithm == KeyAlgorithm::Ed25519
+            && config.match_target == MatchTarget::Body
+            && config.encoding == Encoding::Base64
+            && config.match_scope == MatchScope::Body
+        {
+            ssh::ED25519_BODY_PREFIX
+        } else {
+            ""
+        };
+        let prefix = config.prefix.map(|prefix| format!("{}{}", header, prefix));
+        patterns.push(matcher::affix_pattern(prefix.as_deref(), config.suffix));
+    } else if config.wordlist.is_none() || !config.pattern.is_empty() {
+        patterns.push(config.pattern.to_string());
+    }
+    patterns.extend(config.extra_patterns.iter().map(|s| s.to_string()));
+    if let Some(path) = config.wordlist {
+        match matcher::load_wordlist(Path::new(path)) {
+            Ok(words) => patterns.extend(words),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    let extra_patterns = patterns.split_off(1);
+    let pattern = patterns.remove(0);
 
     // Validate inputs
-    validate_pattern(config.pattern);
+    validate_pattern(&pattern);
+    for pattern in extra_patterns.iter().map(String::as_str).chain(config.near) {
+        validate_pattern(pattern);
+    }
     let cpu_count = num_cpus::get();
     let thread_count = validate_threads(config.threads, cpu_count);
 
-    // Display configuration
-    display_thread_info(thread_count, cpu_count);
+    if let Some(comment) = config.comment {
+        if let Err(e) = ssh::validate_comment(comment) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    // The comment ends up inside the YAML snippet, so reject it up front
+    if config.ansible {
+        if let Some(comment) = config.comment {
+            if let Err(e) = format::validate_yaml_comment(comment) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(options) = config.key_options {
+        if config.append_authorized_keys.is_none() {
+            eprintln!("Error: --key-options requires --append-authorized-keys");
+            std::process::exit(1);
+        }
+        if let Err(e) = ssh::validate_key_options(options) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    // The template points at the saved .pub file, so there must be one
+    if config.cert_template && config.output_dir.is_none() {
+        eprintln!("Error: --cert-template requires --output-dir");
+        std::process::exit(1);
+    }
+
+    if config.match_target != MatchTarget::Body && config.encoding != Encoding::Base64 {
+        eprintln!("Error: fingerprint matching can't be combined with --encoding");
+        std::process::exit(1);
+    }
+
+    if config.match_scope == MatchScope::Full
+        && (config.match_target != MatchTarget::Body || config.encoding != Encoding::Base64)
+    {
+        eprintln!("Error: --match-scope full only applies to the base64 key body");
+        std::process::exit(1);
+    }
+
+    if config.assess && config.algorithm != KeyAlgorithm::Ed25519 {
+        eprintln!("Error: --assess only supports ed25519 keys");
+        std::process::exit(1);
+    }
+
+    if config.verify && config.algorithm != KeyAlgorithm::Ed25519 {
+        eprintln!("Error: --verify only supports ed25519 keys");
+        std::process::exit(1);
+    }
+
+    if config.private_format != PrivateKeyFormat::OpenSsh
+        && config.algorithm != KeyAlgorithm::Ed25519
+    {
+        eprintln!("Error: --private-format pkcs8 and ppk only support ed25519 keys");
+        std::process::exit(1);
+    }
+
+    if let Some((low, high)) = config.range {
+        if low >= high {
+            eprintln!("Error: --range LOW must sort before HIGH");
+            std::process::exit(1);
+        }
+    }
+
+    // A seed makes the run reproducible (and the keys guessable)
+    if config.seed.is_some() && config.seed_file.is_some() {
+        eprintln!("Error: --seed and --seed-file can't be used together");
+        std::process::exit(1);
+    }
+    let seed = config
+        .seed_file
+        .map(|path| match keygen::seed_from_file(Path::new(path)) {
+            Ok(seed) => seed,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        })
+        .or(config.seed.map(keygen::seed_from_u64));
+    if seed.is_some() {
+        eprintln!("Warning: using a fixed seed; generated keys are NOT secret");
+    }
+
+    // Base32 is single-case and hex is lowercase, so only base64 is affected
+    if config.encoding == Encoding::Base64 {
+        if let Some(warning) = std::iter::once(&pattern)
+            .chain(&extra_patterns)
+            .find_map(|pattern| matcher::case_insensitive_warning(pattern, config.case_sensitive))
+        {
+            eprintln!("{}", warning);
+        }
+    }
+
+    // An impossible pattern would search forever
+    if config.algorithm == KeyAlgorithm::Ed25519
+        && config.match_target == MatchTarget::Body
+        && config.encoding == Encoding::Base64
+        && config.match_scope == MatchScope::Body
+    {
+        validate_satisfiable(
+            std::iter::once(pattern.as_str()).chain(extra_patterns.iter().map(String::as_str)),
+            config.case_sensitive,
+            config.strict,
+        );
+    }
+
+    // Block the watched signals before any thread is spawned, so every
+    // thread inherits the mask and only the watchers receive them
+    if !config.dry_run {
+        block_watched_signals(&config);
+    }
+
+    // Detach before any threads are spawned
+    if config.daemon && !config.dry_run {
+        start_daemon(&config);
+    }
+
+    // Display configuration; showing it is all a dry run does
+    if (!config.json && !config.quiet) || config.dry_run {
+        display_thread_info(thread_count, cpu_count);
+        if let KeyAlgorithm::Rsa { bits } = config.algorithm {
+            println!(
+                "Generating {}-bit RSA keys; expect this to be thousands of times slower than ed25519",
+                bits
+            );
+        } else if config.match_target == MatchTarget::Body
+            && extra_patterns.is_empty()
+            && config.encoding == Encoding::Base64
+            && config.match_scope == MatchScope::Body
+            && config.match_length.is_none()
+        {
+            display_estimate(
+                &pattern,
+                config.case_sensitive,
+                thread_count,
+                config.count.unwrap_or(1),
+            );
+        }
+    }
+
+    if config.dry_run {
+        return Ok(());
+    }
+
+    // Installed after detaching, since the handlers run on their own threads
+    let stats_signal = install_stats_handler();
+    let terminate = Arc::new(AtomicBool::new(false));
+    install_interrupt_handler(Arc::clone(&terminate));
+
+    // On hybrid CPUs, leave the slow cores out when not using all of them
+    let pin_cores = if config.prefer_fast_cores {
+        let cores = cores::fastest_cores(thread_count, Duration::from_millis(200));
+        if cores.is_empty() {
+            eprintln!("Warning: can't pin threads on this platform, ignoring --prefer-fast-cores");
+            None
+        } else {
+            if !config.json && !config.quiet {
+                println!("Pinning workers to cores {:?}", cores);
+            }
+            Some(cores)
+        }
+    } else {
+        None
+    };
+
+    let pool_config = ThreadPoolConfig {
+        pattern,
+        extra_patterns,
+        thread_count,
+        case_sensitive: config.case_sensitive,
+        streaming: config.streaming
+            || config.daemon
+            || config.interactive
+            || config.count.is_some(),
+        comment: config.comment.map(|s| s.to_string()),
+        startup_stagger: config.stagger_ms.map(Duration::from_millis),
+        drop_when_full: config.drop_when_full,
+        seed,
+        range: config
+            .range
+            .map(|(low, high)| (low.to_string(), high.to_string())),
+        match_target: config.match_target,
+        encoding: config.encoding,
+        match_scope: config.match_scope,
+        match_length: config.match_length,
+        max_attempts: config.max_attempts,
+        max_rate: config.max_rate,
+        timeout: config.timeout_secs.map(Duration::from_secs),
+        max_matches: config.count,
+        pin_cores,
+        terminate: Some(terminate),
+        safe: config.safe,
+        algorithm: config.algorithm,
+        report_interval: config.report_interval,
+        prefer_exact: config.prefer_exact,
+        near_pattern: config.near.map(str::to_string),
+        // The channel sizes keep their defaults
+        ..Default::default()
+    };
+
+    let output = OutputOptions {
+        output_dir: config.output_dir.map(PathBuf::from),
+        append_authorized_keys: config.append_authorized_keys.map(PathBuf::from),
+        key_options: config.key_options.map(|s| s.to_string()),
+        pub_trailing_newline: config.pub_trailing_newline,
+        ansible: config.ansible,
+        cert_template: config.cert_template,
+        json: config.json,
+        quiet: config.quiet,
+        no_progress: config.no_progress,
+        assess: config.assess,
+        randomart: config.randomart,
+        verify: config.verify,
+        private_format: config.private_format,
+        progress_fifo: config.progress_fifo.map(PathBuf::from),
+        metrics_file: config.metrics_file.map(PathBuf::from),
+        state_file: config.state_file.map(PathBuf::from),
+        receipt_file: config.receipt_file.map(PathBuf::from),
+        seed_source: match (config.seed_file, config.seed) {
+            (Some(path), _) => Some(SeedSource::file(Path::new(path))),
+            (None, Some(_)) => Some(SeedSource::number()),
+            (None, None) => None,
+        },
+        interactive: config.interactive,
+        stats_signal,
+    };
+
+    // Only a limited search can end without finding anything
+    let bounded = config.max_attempts.is_some() || config.timeout_secs.is_some();
 
     // Run the core functionality
-    match stream_openssh_keys_and_match_mt(
-        config.pattern,
-        config.streaming,
-        config.comment,
-        config.case_sensitive,
-        Some(thread_count),
-    ) {
-        Ok(metrics) => {
-            // Format and display performance metrics
-            println!("\nKey generation completed successfully!");
-            println!("----------------------------------------");
-            println!("{}", metrics);
+    let metrics = match run_search(pool_config, &output) {
+        Ok(metrics) => metrics,
+        Err(e) => {
+            eprintln!("\nError during key generation: {}", e);
+            return Err(e);
+        }
+    };
+
+    if config.json {
+        println!("{}", json::to_line(&json::MetricsRecord::from(&metrics))?);
+    } else if config.output_format != OutputFormat::Text {
+        // Asked for by name, so printed even with --quiet
+        println!("{}", metrics.render(config.output_format));
+    } else if !config.quiet {
+        // Format and display performance metrics
+        println!("\nKey generation completed successfully!");
+        println!("----------------------------------------");
+        println!("{}", metrics);
+        println!("Per thread: {}", metrics.per_thread_breakdown());
+        if let Some(near) = config.near {
+            println!("Near misses ({}): {}", near, metrics.near_misses);
+        }
+    }
+
+    // Lets scripts tell a search that gave up from one that found a key
+    if bounded && metrics.matches_found == 0 {
+        if !config.json && !config.quiet {
+            eprintln!("No match found before the limit was reached");
+        }
+        std::process::exit(EXIT_NO_MATCH);
+    }
+    Ok(())
+}
+
+/// Exit code of a `--max-attempts` or `--timeout` run that found no match
+const EXIT_NO_MATCH: i32 = 2;
+
+/// Stop the search on the first Ctrl-C so the final metrics still get
+/// printed, and exit immediately on the second
+fn install_interrupt_handler(terminate: Arc<AtomicBool>) {
+    let result = ctrlc::set_handler(move || {
+        if terminate.swap(true, Ordering::Relaxed) {
+            std::process::exit(130);
+        }
+        eprintln!("\nInterrupted, stopping search (press Ctrl-C again to exit immediately)");
+    });
+    if let Err(e) = result {
+        eprintln!("Warning: could not install Ctrl-C handler: {}", e);
+    }
+}
+
+/// Block SIGUSR1, and SIGTERM for `--daemon`, in the main thread, exiting on
+/// failure. SIGTERM stays deliverable otherwise so a normal run can be killed.
+#[cfg(unix)]
+fn block_watched_signals(config: &Config) {
+    let result = if config.daemon {
+        signals::block(&[libc::SIGUSR1, libc::SIGTERM])
+    } else {
+        signals::block(&[libc::SIGUSR1])
+    };
+    if let Err(e) = result {
+        eprintln!("Error: could not block signals: {}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(not(unix))]
+fn block_watched_signals(_config: &Config) {}
+
+/// Print a stats snapshot to stderr on SIGUSR1, without stopping the search
+#[cfg(unix)]
+fn install_stats_handler() -> Option<Arc<AtomicBool>> {
+    Some(signals::spawn_stats_watcher())
+}
+
+#[cfg(not(unix))]
+fn install_stats_handler() -> Option<Arc<AtomicBool>> {
+    None
+}
+
+/// Detach into the background for `--daemon`, exiting on failure
+#[cfg(unix)]
+fn start_daemon(config: &Config) {
+    let pid_file = Path::new(config.pid_file.unwrap_or(daemon::DEFAULT_PID_FILE));
+    let log_file = Path::new(config.log_file.unwrap_or(daemon::DEFAULT_LOG_FILE));
+
+    if let Err(e) = daemon::daemonize(pid_file, log_file) {
+        eprintln!("Error: could not start daemon: {}", e);
+        std::process::exit(1);
+    }
+    daemon::spawn_stop_watcher(pid_file);
+}
+
+#[cfg(not(unix))]
+fn start_daemon(config: &Config) {
+    // The daemon file options are only used on Unix
+    let _ = (config.pid_file, config.log_file);
+    eprintln!("Error: --daemon is only supported on Unix");
+    std::process::exit(1);
+}
+
+/// Handle `--stop`, signalling the daemon recorded in the PID file
+#[cfg(unix)]
+fn stop_daemon(pid_file: Option<&str>) -> Result<()> {
+    let pid_file = pid_file.unwrap_or(daemon::DEFAULT_PID_FILE);
+
+    match daemon::stop(Path::new(pid_file)) {
+        Ok(pid) => {
+            println!("Stopped daemon (PID {})", pid);
             Ok(())
         }
         Err(e) => {
-            eprintln!("\nError during key generation: {}", e);
-            Err(e)
+            eprintln!("Error: could not stop daemon: {}", e);
+            Err(e.into())
         }
     }
 }
+
+#[cfg(not(unix))]
+fn stop_daemon(_pid_file: Option<&str>) -> Result<()> {
+    eprintln!("Error: --stop is only supported on Unix");
+    std::process::exit(1);
+}
+
+/// Sends `log` output from the search to stderr when `-v` is given: debug
+/// lines for matches, and with `-vv` trace lines for status updates too
+fn init_logger(verbose: u8) {
+    let level = match verbose {
+        0 => return,
+        1 => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
+    };
+    env_logger::Builder::new()
+        .filter_module("vanityssh_rust", level)
+        .target(env_logger::Target::Stderr)
+        .init();
+}
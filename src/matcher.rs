@@ -2,17 +2,115 @@
 // Updated: 2025-04-22 14:15:00 by kengggg
 
 use crate::error::{Result, VanityError};
-use crate::ssh::public_key::extract_ssh_key_data;
-use regex::Regex;
+use crate::ssh::public_key::{extract_ssh_key_data, fingerprint_sha256};
+use crate::ssh::{ED25519_BODY_PREFIX, ED25519_KEY_TYPE};
+use crate::stats;
+use base64::{engine::general_purpose, Engine};
+use regex::{Regex, RegexSet};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
 
-/// Checks if a string matches a regex pattern.
-///
-/// If case_sensitive is false, the pattern is treated as case-insensitive.
-pub fn matches_pattern(key: &str, pattern: &str, case_sensitive: bool) -> Result<bool> {
+/// Length of the Ed25519 public key blob: a length-prefixed key type
+/// followed by the length-prefixed 32-byte key
+const ED25519_BLOB_LEN: usize = 4 + ED25519_KEY_TYPE.len() + 4 + 32;
+
+/// Length of the base64 encoding of the Ed25519 public key blob
+const ED25519_BODY_LEN: usize = ED25519_BLOB_LEN.div_ceil(3) * 4;
+
+/// Which rendering of a public key a pattern is matched against
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum MatchTarget {
+    /// The base64 body of the OpenSSH public key line
+    #[default]
+    Body,
+    /// The base64 SHA256 fingerprint shown by `ssh-keygen -l`, without the
+    /// `SHA256:` label
+    Fingerprint,
+    /// The same SHA256 digest as lowercase hex, like a short hash
+    FingerprintHex,
+}
+
+impl FromStr for MatchTarget {
+    type Err = VanityError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "body" => Ok(MatchTarget::Body),
+            "fingerprint" => Ok(MatchTarget::Fingerprint),
+            "fingerprint-hex" => Ok(MatchTarget::FingerprintHex),
+            _ => Err(VanityError::InvalidFormat(format!(
+                "unknown match target '{}', expected body, fingerprint or fingerprint-hex",
+                s
+            ))),
+        }
+    }
+}
+
+/// How much of the public key line a pattern is matched against
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum MatchScope {
+    /// Only the base64 body
+    #[default]
+    Body,
+    /// The key type, a space and the base64 body, e.g.
+    /// `ssh-ed25519 AAAAC3...`, leaving out the comment
+    Full,
+}
+
+impl FromStr for MatchScope {
+    type Err = VanityError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "body" => Ok(MatchScope::Body),
+            "full" => Ok(MatchScope::Full),
+            _ => Err(VanityError::InvalidFormat(format!(
+                "unknown match scope '{}', expected body or full",
+                s
+            ))),
+        }
+    }
+}
+
+/// How the raw public key bytes are rendered for matching
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Encoding {
+    /// The base64 body of the OpenSSH public key line
+    #[default]
+    Base64,
+    /// RFC 4648 base32 of the raw key, uppercase and without padding. Having
+    /// a single case makes it friendlier for spelled-out words.
+    Base32,
+    /// Lowercase hex of the raw key
+    Hex,
+}
+
+impl FromStr for Encoding {
+    type Err = VanityError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "base64" => Ok(Encoding::Base64),
+            "base32" => Ok(Encoding::Base32),
+            "hex" => Ok(Encoding::Hex),
+            _ => Err(VanityError::InvalidFormat(format!(
+                "unknown encoding '{}', expected base64, base32 or hex",
+                s
+            ))),
+        }
+    }
+}
+
+/// Returns the pattern with the `(?i)` flag added or removed to reflect
+/// the requested case sensitivity.
+pub fn effective_pattern(pattern: &str, case_sensitive: bool) -> String {
     // Handle case sensitivity properly:
     // 1. If we want case-insensitive matching, add (?i) if not already there
     // 2. If we want case-sensitive matching, ensure (?i) is not present
-    let effective_pattern = if case_sensitive {
+    if case_sensitive {
         // Remove (?i) prefix if it exists for case-sensitive matching
         if let Some(stripped) = pattern.strip_prefix("(?i)") {
             stripped.to_string()
@@ -26,24 +124,655 @@ pub fn matches_pattern(key: &str, pattern: &str, case_sensitive: bool) -> Result
         } else {
             format!("(?i){}", pattern)
         }
-    };
+    }
+}
 
-    // Compile the regex pattern
-    let regex = match Regex::new(&effective_pattern) {
-        Ok(r) => r,
-        Err(e) => return Err(VanityError::InvalidRegex(e.to_string())),
-    };
+/// Checks if a string matches a regex pattern.
+///
+/// If case_sensitive is false, the pattern is treated as case-insensitive.
+/// Base64 is case-significant, so a case-insensitive `abc` also accepts a
+/// key showing `ABC` or `aBc`; pass true to match the literal characters.
+pub fn matches_pattern(key: &str, pattern: &str, case_sensitive: bool) -> Result<bool> {
+    // A plain `^literal` needs no regex at all
+    if let Some(matched) = anchored_literal_matches(key, pattern, case_sensitive) {
+        return Ok(matched);
+    }
+
+    let regex = compile_pattern(pattern, case_sensitive)?;
 
     // Check if the key matches the pattern
-    Ok(regex.is_match(key))
+    Ok(matches_compiled(key, &regex))
+}
+
+/// Compiles a pattern once, applying the same case-sensitivity handling as
+/// `matches_pattern`, for matching many keys with `matches_compiled`.
+pub fn compile_pattern(pattern: &str, case_sensitive: bool) -> Result<Regex> {
+    Regex::new(&effective_pattern(pattern, case_sensitive))
+        .map_err(|e| VanityError::InvalidRegex(e.to_string()))
+}
+
+/// Compiles several patterns into one `RegexSet`, with the same
+/// case-sensitivity handling as `compile_pattern`, so a key can be checked
+/// against all of them in a single pass.
+pub fn compile_pattern_set(patterns: &[String], case_sensitive: bool) -> Result<RegexSet> {
+    RegexSet::new(
+        patterns
+            .iter()
+            .map(|pattern| effective_pattern(pattern, case_sensitive)),
+    )
+    .map_err(|e| VanityError::InvalidRegex(e.to_string()))
+}
+
+/// Whether `pattern` matches every string, like `.*` or the empty pattern,
+/// so matching can be skipped altogether.
+///
+/// Only a few spellings are recognised: an empty pattern, `.*`, `^` and `$`
+/// on their own or around `.*`, each optionally after `(?i)`.
+pub fn matches_everything(pattern: &str) -> bool {
+    let pattern = pattern.strip_prefix("(?i)").unwrap_or(pattern);
+    matches!(pattern, "" | ".*" | "^" | "$" | "^.*" | ".*$" | "^.*$")
+}
+
+/// Checks if a string matches a pattern compiled with `compile_pattern`
+pub fn matches_compiled(key: &str, regex: &Regex) -> bool {
+    regex.is_match(key)
 }
 
 /// Checks if an SSH public key matches a regex pattern.
 /// The function extracts the base64-encoded part of the key and matches against that.
 ///
-/// If case_sensitive is false, the pattern is treated as case-insensitive.
+/// If case_sensitive is false, the pattern is treated as case-insensitive,
+/// so the key may show the pattern's letters in any mix of cases (see
+/// `case_insensitive_warning`).
 pub fn ssh_key_matches_pattern(ssh_key: &str, pattern: &str, case_sensitive: bool) -> Result<bool> {
-    // Extract the base64 part and match against that
+    ssh_key_matches_pattern_in(ssh_key, pattern, case_sensitive, MatchScope::Body)
+}
+
+/// Like `ssh_key_matches_pattern`, but matches against as much of the key
+/// line as `scope` selects
+pub fn ssh_key_matches_pattern_in(
+    ssh_key: &str,
+    pattern: &str,
+    case_sensitive: bool,
+    scope: MatchScope,
+) -> Result<bool> {
+    matches_pattern(&scoped_text(ssh_key, scope)?, pattern, case_sensitive)
+}
+
+/// Like `ssh_key_matches_pattern`, but only the first `match_length`
+/// characters of the body are matched, so a pattern can't match further in
+/// and `$` anchors at the cut
+pub fn ssh_key_matches_pattern_truncated(
+    ssh_key: &str,
+    pattern: &str,
+    case_sensitive: bool,
+    match_length: usize,
+) -> Result<bool> {
+    let body = extract_ssh_key_data(ssh_key)?;
+    matches_pattern(
+        truncate_text(&body, Some(match_length)),
+        pattern,
+        case_sensitive,
+    )
+}
+
+/// Returns at most the first `match_length` characters of `text`, or all of
+/// it for `None`
+pub fn truncate_text(text: &str, match_length: Option<usize>) -> &str {
+    match match_length {
+        Some(length) => match text.char_indices().nth(length) {
+            Some((end, _)) => &text[..end],
+            None => text,
+        },
+        None => text,
+    }
+}
+
+/// Returns the part of an SSH public key line that `scope` selects
+pub fn scoped_text(ssh_key: &str, scope: MatchScope) -> Result<String> {
+    let body = extract_ssh_key_data(ssh_key)?;
+    match scope {
+        MatchScope::Body => Ok(body),
+        MatchScope::Full => {
+            // extract_ssh_key_data has already checked the key type
+            let key_type = ssh_key.split_whitespace().next().unwrap_or_default();
+            Ok(format!("{} {}", key_type, body))
+        }
+    }
+}
+
+/// Returns the text of an SSH public key that a pattern is matched against
+/// for the given target.
+pub fn match_text(ssh_key: &str, target: MatchTarget) -> Result<String> {
+    match target {
+        MatchTarget::Body => extract_ssh_key_data(ssh_key),
+        MatchTarget::Fingerprint => body_fingerprint(&extract_ssh_key_data(ssh_key)?),
+        MatchTarget::FingerprintHex => body_fingerprint_hex(&extract_ssh_key_data(ssh_key)?),
+    }
+}
+
+/// Returns the base64 SHA256 fingerprint, without the `SHA256:` label, of the
+/// key whose public key line has the base64 body `body`
+pub fn body_fingerprint(body: &str) -> Result<String> {
+    let blob = general_purpose::STANDARD
+        .decode(body)
+        .map_err(|e| VanityError::EncodingError(e.to_string()))?;
+    let fingerprint = fingerprint_sha256(&blob);
+    Ok(fingerprint
+        .strip_prefix("SHA256:")
+        .unwrap_or(&fingerprint)
+        .to_string())
+}
+
+/// Returns the SHA256 digest of a public key blob as lowercase hex
+pub fn fingerprint_hex(blob: &[u8]) -> String {
+    hex::encode(Sha256::digest(blob))
+}
+
+/// Like `body_fingerprint`, but returns the digest as lowercase hex
+pub fn body_fingerprint_hex(body: &str) -> Result<String> {
+    let blob = general_purpose::STANDARD
+        .decode(body)
+        .map_err(|e| VanityError::EncodingError(e.to_string()))?;
+    Ok(fingerprint_hex(&blob))
+}
+
+/// Checks if the hex SHA256 digest of a public key blob matches a regex
+/// pattern, so `^cafe` finds a key whose digest starts with `cafe`.
+///
+/// If case_sensitive is false, the pattern is treated as case-insensitive.
+pub fn fingerprint_hex_matches(blob: &[u8], pattern: &str, case_sensitive: bool) -> Result<bool> {
+    matches_pattern(&fingerprint_hex(blob), pattern, case_sensitive)
+}
+
+/// Renders a raw Ed25519 public key in the given encoding.
+///
+/// `Base64` gives the body of the key's OpenSSH public key line, including
+/// its fixed header, so it agrees with the default matching.
+pub fn encode_key(public_key_bytes: &[u8], encoding: Encoding) -> Result<String> {
+    match encoding {
+        Encoding::Base64 => {
+            let ssh_key = crate::ssh::encode_ssh_public_key(public_key_bytes, None)?;
+            extract_ssh_key_data(&ssh_key)
+        }
+        Encoding::Base32 => Ok(base32::encode(
+            base32::Alphabet::Rfc4648 { padding: false },
+            public_key_bytes,
+        )),
+        Encoding::Hex => Ok(hex::encode(public_key_bytes)),
+    }
+}
+
+/// Checks if the rendering of a raw public key in `encoding` matches a regex
+/// pattern.
+///
+/// If case_sensitive is false, the pattern is treated as case-insensitive.
+pub fn encoded_key_matches(
+    public_key_bytes: &[u8],
+    pattern: &str,
+    encoding: Encoding,
+    case_sensitive: bool,
+) -> Result<bool> {
+    let text = encode_key(public_key_bytes, encoding)?;
+    matches_pattern(&text, pattern, case_sensitive)
+}
+
+/// Returns a warning to show once before a case-insensitive search of
+/// base64 text, or `None` if case can't affect the match.
+///
+/// Only letters in the pattern are affected; escapes such as `\d` are
+/// ignored.
+pub fn case_insensitive_warning(pattern: &str, case_sensitive: bool) -> Option<&'static str> {
+    if case_sensitive {
+        return None;
+    }
+
+    let pattern = pattern.strip_prefix("(?i)").unwrap_or(pattern);
+    let mut escaped = false;
+    let has_letter = pattern.chars().any(|c| {
+        let literal_letter = !escaped && c.is_ascii_alphabetic();
+        escaped = !escaped && c == '\\';
+        literal_letter
+    });
+
+    has_letter.then_some(
+        "Warning: matching is case-insensitive, so the key may show your pattern in any mix of \
+         upper and lower case (and look-alikes such as l and I are easy to misread). \
+         Use --exact-case to match the literal characters.",
+    )
+}
+
+/// Checks if the SHA256 fingerprint of an SSH public key matches a regex pattern.
+/// The pattern is matched against the base64 digest, without the `SHA256:` label.
+///
+/// If case_sensitive is false, the pattern is treated as case-insensitive.
+pub fn fingerprint_matches_pattern(
+    ssh_key: &str,
+    pattern: &str,
+    case_sensitive: bool,
+) -> Result<bool> {
+    let fingerprint = match_text(ssh_key, MatchTarget::Fingerprint)?;
+    matches_pattern(&fingerprint, pattern, case_sensitive)
+}
+
+/// Returns the named capture groups of a pattern matched against a string,
+/// or `None` if the pattern doesn't match.
+///
+/// Only groups that participated in the match are returned, in the order they
+/// appear in the pattern, as `(name, value)` pairs. A pattern without named
+/// groups yields an empty list when it matches.
+pub fn pattern_captures(
+    key: &str,
+    pattern: &str,
+    case_sensitive: bool,
+) -> Result<Option<Vec<(String, String)>>> {
+    let regex = compile_pattern(pattern, case_sensitive)?;
+    Ok(captures_compiled(key, &regex))
+}
+
+/// Like `pattern_captures`, for a pattern compiled with `compile_pattern`
+pub fn captures_compiled(key: &str, regex: &Regex) -> Option<Vec<(String, String)>> {
+    let captures = regex.captures(key)?;
+
+    let named = regex
+        .capture_names()
+        .flatten()
+        .filter_map(|name| {
+            captures
+                .name(name)
+                .map(|m| (name.to_string(), m.as_str().to_string()))
+        })
+        .collect();
+
+    Some(named)
+}
+
+/// Returns the named capture groups of a pattern matched against the
+/// base64-encoded part of an SSH public key.
+pub fn ssh_key_pattern_captures(
+    ssh_key: &str,
+    pattern: &str,
+    case_sensitive: bool,
+) -> Result<Option<Vec<(String, String)>>> {
     let base64_part = extract_ssh_key_data(ssh_key)?;
-    matches_pattern(&base64_part, pattern, case_sensitive)
+    pattern_captures(&base64_part, pattern, case_sensitive)
+}
+
+/// Checks whether the base64 body of the SSH public key for `public_key_bytes`
+/// starts with `prefix`, without encoding the whole key blob.
+///
+/// Only the leading blob bytes needed to produce `prefix.len()` base64
+/// characters are encoded, which makes the (overwhelmingly common) no-match
+/// case much cheaper than formatting the full key. The prefix is compared
+/// against the whole body, including the fixed `AAAAC3NzaC1lZDI1NTE5AAAAI`
+/// header every Ed25519 key starts with.
+pub fn prefix_matches_raw(public_key_bytes: &[u8; 32], prefix: &str, case_sensitive: bool) -> bool {
+    let prefix = prefix.as_bytes();
+    if prefix.len() > ED25519_BODY_LEN {
+        return false;
+    }
+
+    // Assemble the blob on the stack
+    let mut blob = [0u8; ED25519_BLOB_LEN];
+    let type_len = ED25519_KEY_TYPE.len();
+    blob[..4].copy_from_slice(&(type_len as u32).to_be_bytes());
+    blob[4..4 + type_len].copy_from_slice(ED25519_KEY_TYPE.as_bytes());
+    blob[4 + type_len..8 + type_len].copy_from_slice(&32u32.to_be_bytes());
+    blob[8 + type_len..].copy_from_slice(public_key_bytes);
+
+    // Every 3 input bytes produce 4 base64 characters
+    let needed = (prefix.len().div_ceil(4) * 3).min(ED25519_BLOB_LEN);
+    let mut encoded = [0u8; ED25519_BODY_LEN];
+    let written = match general_purpose::STANDARD.encode_slice(&blob[..needed], &mut encoded) {
+        Ok(n) => n,
+        Err(_) => return false,
+    };
+
+    let candidate = &encoded[..written.min(prefix.len())];
+    if candidate.len() < prefix.len() {
+        return false;
+    }
+
+    if case_sensitive {
+        candidate == prefix
+    } else {
+        candidate.eq_ignore_ascii_case(prefix)
+    }
+}
+
+/// Checks whether `region` sorts within the half-open range `[low, high)`.
+///
+/// Strings are compared byte by byte (ASCII order, so `+` < `/` < digits <
+/// uppercase < lowercase). Because the range is half-open, machines given
+/// adjacent ranges such as `[A, M)` and `[M, a)` never search the same keys.
+pub fn in_lex_range(region: &str, low: &str, high: &str) -> bool {
+    region >= low && region < high
+}
+
+/// Returns the part of an Ed25519 public key body that follows the fixed
+/// `AAAAC3NzaC1lZDI1NTE5AAAAI` header, i.e. the region that varies per key.
+pub fn variable_region(base64_body: &str) -> &str {
+    base64_body
+        .strip_prefix(ED25519_BODY_PREFIX)
+        .unwrap_or(base64_body)
+}
+
+/// Built-in list of offensive substrings rejected by `--safe`
+const BLOCKLIST: &str = include_str!("blocklist.txt");
+
+/// Returns the entries of the built-in blocklist, in lowercase
+pub fn blocklist() -> impl Iterator<Item = &'static str> {
+    BLOCKLIST
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// Checks whether `text` contains any blocklisted substring, ignoring case
+pub fn contains_blocked_word(text: &str) -> bool {
+    let text = text.to_ascii_lowercase();
+    blocklist().any(|word| text.contains(word))
+}
+
+/// Checks whether the base64-encoded part of an SSH public key contains any
+/// blocklisted substring, ignoring case
+pub fn ssh_key_contains_blocked_word(ssh_key: &str) -> Result<bool> {
+    let key_data = extract_ssh_key_data(ssh_key)?;
+    Ok(contains_blocked_word(&key_data))
+}
+
+/// Reads a wordlist of patterns, one per line. Blank lines and lines starting
+/// with `#` are skipped, like in the built-in blocklist. Fails if the file
+/// holds no patterns at all.
+pub fn load_wordlist(path: &Path) -> Result<Vec<String>> {
+    let contents = fs::read_to_string(path)?;
+    let patterns: Vec<String> = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect();
+
+    if patterns.is_empty() {
+        return Err(VanityError::InvalidFormat(format!(
+            "wordlist {} contains no patterns",
+            path.display()
+        )));
+    }
+    Ok(patterns)
+}
+
+/// How hard a pattern is to match: the chance that one random key matches,
+/// and the number of keys expected before one does
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Difficulty {
+    /// Chance that a single random key matches, zero for an impossible
+    /// pattern
+    pub per_key_probability: f64,
+    /// Keys generated on average before one matches, infinite for an
+    /// impossible pattern
+    pub expected_attempts: f64,
+}
+
+impl Difficulty {
+    /// The difficulty of a pattern matched once every `expected_attempts`
+    /// keys on average
+    pub fn from_expected_attempts(expected_attempts: f64) -> Self {
+        Difficulty {
+            per_key_probability: 1.0 / expected_attempts,
+            expected_attempts,
+        }
+    }
+
+    /// Whether any key can match at all
+    pub fn is_possible(&self) -> bool {
+        self.expected_attempts.is_finite()
+    }
+
+    /// The per-key chance as odds, e.g. `1 in 16.7M`
+    pub fn odds(&self) -> String {
+        if self.is_possible() {
+            format!("1 in {}", stats::format_count(self.expected_attempts))
+        } else {
+            "never".to_string()
+        }
+    }
+}
+
+/// Formats the expected attempts, e.g. `16.7M attempts`, or `impossible`
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.is_possible() {
+            return f.write_str("impossible");
+        }
+        let count = stats::format_count(self.expected_attempts);
+        if count == "1" {
+            write!(f, "1 attempt")
+        } else {
+            write!(f, "{} attempts", count)
+        }
+    }
+}
+
+/// Estimates how many keys have to be generated, on average, before one
+/// matches `pattern` in its base64 body.
+///
+/// Only patterns of the form `^literal` are understood, where the literal is
+/// made of base64 characters (`+` written as `\+`). Characters inside the
+/// fixed `AAAAC3NzaC1lZDI1NTE5AAAAI` header must agree with it, the first
+/// variable character takes one of 16 values, and every other character one
+/// of 64, or effectively 32 for a letter matched case-insensitively.
+///
+/// Returns `None` for any other pattern, and an impossible difficulty for a
+/// prefix that no key can have.
+pub fn estimate_difficulty(pattern: &str, case_sensitive: bool) -> Option<Difficulty> {
+    let pattern = pattern.strip_prefix("(?i)").unwrap_or(pattern);
+    let literal = anchored_literal(pattern)?;
+    if literal.len() > ED25519_BODY_LEN {
+        return Some(Difficulty::from_expected_attempts(f64::INFINITY));
+    }
+
+    let same = |a: char, b: char| {
+        if case_sensitive {
+            a == b
+        } else {
+            a.eq_ignore_ascii_case(&b)
+        }
+    };
+
+    let fixed: Vec<char> = ED25519_BODY_PREFIX.chars().collect();
+    let mut expected = 1.0;
+    for (position, &c) in literal.iter().enumerate() {
+        let (choices, matching) = if position < fixed.len() {
+            (1, usize::from(same(c, fixed[position])))
+        } else if position == fixed.len() {
+            // The top bits of the first variable character are fixed, so it
+            // is always one of 'A'..='P'
+            (16, ('A'..='P').filter(|&v| same(c, v)).count())
+        } else if !case_sensitive && c.is_ascii_alphabetic() {
+            (64, 2)
+        } else {
+            (64, 1)
+        };
+        if matching == 0 {
+            return Some(Difficulty::from_expected_attempts(f64::INFINITY));
+        }
+        expected *= choices as f64 / matching as f64;
+    }
+
+    Some(Difficulty::from_expected_attempts(expected))
+}
+
+/// Explains why no Ed25519 key body can match `pattern`, or returns `None`
+/// if one might.
+///
+/// Only anchored literals are judged: `^literal`, `literal$` or
+/// `^literal$`, where the literal has no regex operators (escaped ones are
+/// taken literally). Such a pattern can't match if it has a character that
+/// base64 never produces, including `=` since Ed25519 bodies are never
+/// padded, if it's longer than a body, or if it's a `^` prefix the fixed
+/// `AAAAC3NzaC1lZDI1NTE5AAAAI` header rules out (see `estimate_difficulty`).
+pub fn unsatisfiable_reason(pattern: &str, case_sensitive: bool) -> Option<String> {
+    let unflagged = pattern.strip_prefix("(?i)").unwrap_or(pattern);
+    let starts = unflagged.strip_prefix('^');
+    let inner = starts.unwrap_or(unflagged);
+    let inner = match inner.strip_suffix('$') {
+        Some(inner) if !inner.ends_with('\\') => inner,
+        _ if starts.is_some() => inner,
+        _ => return None,
+    };
+
+    let mut length = 0;
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        length += 1;
+        let c = match c {
+            '\\' => match chars.next() {
+                Some(escaped) if escaped.is_ascii_punctuation() => escaped,
+                // A class like \d or \w, not a literal
+                _ => return None,
+            },
+            '.' | '+' | '*' | '?' | '(' | ')' | '|' | '[' | ']' | '{' | '}' | '^' | '$' => {
+                return None
+            }
+            c => c,
+        };
+        if !(c.is_ascii_alphanumeric() || c == '+' || c == '/') {
+            return Some(format!("'{}' never appears in a base64 key body", c));
+        }
+    }
+
+    if length > ED25519_BODY_LEN {
+        return Some(format!(
+            "it is longer than the {} characters of a key body",
+            ED25519_BODY_LEN
+        ));
+    }
+    // Only a prefix can contradict the header
+    starts?;
+    estimate_difficulty(&format!("^{}", inner), case_sensitive)
+        .filter(|difficulty| !difficulty.is_possible())
+        .map(|_| "it contradicts the fixed key header".to_string())
+}
+
+/// Whether some Ed25519 key body could match `pattern`; see
+/// `unsatisfiable_reason`
+pub fn pattern_is_satisfiable(pattern: &str, case_sensitive: bool) -> bool {
+    unsatisfiable_reason(pattern, case_sensitive).is_none()
+}
+
+/// Builds the pattern for `--prefix` and `--suffix`: the text must start
+/// with `prefix` and end with `suffix`, both taken literally. Either may be
+/// left out.
+pub fn affix_pattern(prefix: Option<&str>, suffix: Option<&str>) -> String {
+    let mut pattern = String::new();
+    if let Some(prefix) = prefix {
+        pattern.push('^');
+        pattern.push_str(&regex::escape(prefix));
+    }
+    if let Some(suffix) = suffix {
+        if prefix.is_some() {
+            pattern.push_str(".*");
+        }
+        pattern.push_str(&regex::escape(suffix));
+        pattern.push('$');
+    }
+    pattern
+}
+
+/// Checks a `^literal`, `literal$` or `^literal.*literal$` pattern (the
+/// shapes `affix_pattern` builds) with plain prefix and suffix comparisons,
+/// giving the same answer as the regex would. Returns `None` if the pattern
+/// is anything else.
+pub(crate) fn anchored_literal_matches(
+    key: &str,
+    pattern: &str,
+    case_sensitive: bool,
+) -> Option<bool> {
+    AnchoredLiteral::parse(pattern, case_sensitive).map(|literal| literal.matches(key))
+}
+
+/// A `^literal`, `literal$` or `^literal.*literal$` pattern taken apart
+/// once, for matching many keys with `matches` instead of a regex
+#[derive(Debug, Clone)]
+pub(crate) struct AnchoredLiteral {
+    /// The text a match starts with, empty for `literal$`
+    pub(crate) prefix: String,
+    /// The text a match ends with, empty for `^literal`
+    pub(crate) suffix: String,
+    pub(crate) case_sensitive: bool,
+}
+
+impl AnchoredLiteral {
+    /// Returns `None` if the pattern has any other shape
+    pub(crate) fn parse(pattern: &str, case_sensitive: bool) -> Option<Self> {
+        let pattern = pattern.strip_prefix("(?i)").unwrap_or(pattern);
+        let (prefix, suffix) = match pattern.strip_prefix('^') {
+            Some(rest) => match rest.strip_suffix('$') {
+                Some(inner) => inner.split_once(".*")?,
+                None => (rest, ""),
+            },
+            None => ("", pattern.strip_suffix('$')?),
+        };
+        if !is_base64_literal(prefix) || !is_base64_literal(suffix) {
+            return None;
+        }
+        Some(AnchoredLiteral {
+            prefix: literal_chars(prefix).collect(),
+            suffix: literal_chars(suffix).collect(),
+            case_sensitive,
+        })
+    }
+
+    /// Whether `key` starts with the prefix and ends with the suffix, with
+    /// the two not overlapping
+    pub(crate) fn matches(&self, key: &str) -> bool {
+        let key = key.as_bytes();
+        let (prefix, suffix) = (self.prefix.as_bytes(), self.suffix.as_bytes());
+        if key.len() < prefix.len() + suffix.len() {
+            return false;
+        }
+        let eq = |actual: &[u8], expected: &[u8]| {
+            if self.case_sensitive {
+                actual == expected
+            } else {
+                actual.eq_ignore_ascii_case(expected)
+            }
+        };
+        eq(&key[..prefix.len()], prefix) && eq(&key[key.len() - suffix.len()..], suffix)
+    }
+}
+
+/// Returns the literal of a `^literal` pattern over the base64 alphabet, or
+/// `None` if the pattern is anything else
+fn anchored_literal(pattern: &str) -> Option<Vec<char>> {
+    let literal = pattern.strip_prefix('^')?;
+    is_base64_literal(literal).then(|| literal_chars(literal).collect())
+}
+
+/// Whether a pattern (without its anchor) is only base64 characters, with
+/// `+` and optionally `/` escaped by a backslash
+fn is_base64_literal(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if !matches!(chars.next(), Some('+' | '/')) {
+                    return false;
+                }
+            }
+            c if c == '/' || c.is_ascii_alphanumeric() => {}
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// The characters a literal pattern stands for, with escapes removed
+fn literal_chars(pattern: &str) -> impl Iterator<Item = char> + '_ {
+    let mut chars = pattern.chars();
+    std::iter::from_fn(move || match chars.next()? {
+        '\\' => chars.next(),
+        c => Some(c),
+    })
 }
@@ -0,0 +1,87 @@
+// src/output.rs
+
+use crate::error::Result;
+use crate::ssh::public_key::authorized_keys_line;
+use crate::ssh::RSA_KEY_TYPE;
+use crate::thread_pool::KeyMatch;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Writes a matched key pair to `dir` as `id_ed25519_<n>` and `id_ed25519_<n>.pub`,
+/// or `id_rsa_<n>` and `id_rsa_<n>.pub` for RSA keys.
+///
+/// `n` starts at `start_index` and is bumped past any index that already has
+/// a file in the directory, so neither streaming mode nor a second run ever
+/// overwrites earlier keys. The directory is created if it doesn't exist and
+/// the private key is only readable by its owner on Unix.
+///
+/// Returns the index used along with the private and public key paths.
+pub fn save_key_pair(
+    dir: &Path,
+    start_index: u64,
+    key_match: &KeyMatch,
+    pub_trailing_newline: bool,
+) -> Result<(u64, PathBuf, PathBuf)> {
+    fs::create_dir_all(dir)?;
+
+    let stem = if key_match.public_key.starts_with(RSA_KEY_TYPE) {
+        "id_rsa"
+    } else {
+        "id_ed25519"
+    };
+
+    let mut index = start_index;
+    let (private_path, public_path) = loop {
+        let private_path = dir.join(format!("{}_{}", stem, index));
+        let public_path = dir.join(format!("{}_{}.pub", stem, index));
+        if !private_path.exists() && !public_path.exists() {
+            break (private_path, public_path);
+        }
+        index += 1;
+    };
+
+    let mut private_contents = key_match.private_key.clone();
+    private_contents.push('\n');
+    write_file(&private_path, private_contents.as_bytes(), 0o600)?;
+
+    let public_contents = if pub_trailing_newline {
+        authorized_keys_line(&key_match.public_key)
+    } else {
+        key_match.public_key.clone()
+    };
+    write_file(&public_path, public_contents.as_bytes(), 0o644)?;
+
+    Ok((index, private_path, public_path))
+}
+
+/// Helper function to create a new file with the given Unix permissions.
+///
+/// The contents are written to a temporary file in the same directory and
+/// then moved into place, so a crash never leaves a truncated key behind;
+/// readers see either no file or the complete one. Fails if `path` exists.
+fn write_file(path: &Path, contents: &[u8], mode: u32) -> Result<()> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    // The temporary file starts out readable only by its owner
+    let mut temp = tempfile::Builder::new()
+        .prefix(".vanityssh-")
+        .tempfile_in(dir)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        temp.as_file()
+            .set_permissions(fs::Permissions::from_mode(mode))?;
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    temp.write_all(contents)?;
+    temp.as_file().sync_all()?;
+    temp.persist_noclobber(path).map_err(|e| e.error)?;
+    Ok(())
+}
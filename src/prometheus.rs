@@ -0,0 +1,50 @@
+// src/prometheus.rs
+use crate::error::Result;
+use crate::PerformanceMetrics;
+use std::io::Write;
+use std::path::Path;
+
+/// Renders the metrics in the Prometheus text format
+pub fn render(metrics: &PerformanceMetrics) -> String {
+    format!(
+        "# HELP vanityssh_attempts_total Keys generated so far.\n\
+         # TYPE vanityssh_attempts_total counter\n\
+         vanityssh_attempts_total {}\n\
+         # HELP vanityssh_matches_total Matching keys found so far.\n\
+         # TYPE vanityssh_matches_total counter\n\
+         vanityssh_matches_total {}\n\
+         # HELP vanityssh_keys_per_second Average key generation rate.\n\
+         # TYPE vanityssh_keys_per_second gauge\n\
+         vanityssh_keys_per_second {}\n",
+        metrics.attempts, metrics.matches_found, metrics.keys_per_second
+    )
+}
+
+/// Writes the metrics to `path` for a textfile collector such as
+/// node_exporter's.
+///
+/// The file is replaced atomically by renaming a temporary file from the
+/// same directory over it, so a scrape never sees a half-written file.
+pub fn write_textfile(path: &Path, metrics: &PerformanceMetrics) -> Result<()> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let mut temp = tempfile::Builder::new()
+        .prefix(".vanityssh-metrics-")
+        .tempfile_in(dir)?;
+
+    // The collector usually runs as another user
+    #[cfg(unix)]
+    {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+        temp.as_file()
+            .set_permissions(fs::Permissions::from_mode(0o644))?;
+    }
+
+    temp.write_all(render(metrics).as_bytes())?;
+    temp.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
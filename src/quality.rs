@@ -0,0 +1,51 @@
+// src/quality.rs
+use crate::error::{Result, VanityError};
+use crate::ssh::public_key::decode_ssh_public_key;
+use ed25519_dalek::VerifyingKey;
+use std::fmt;
+
+/// Outcome of the sanity checks `--assess` runs on a matched public key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyQuality {
+    /// A valid point outside the small-order subgroup
+    Healthy,
+    /// The public key is all zero bytes
+    AllZero,
+    /// The public key is the identity or another point of small order, for
+    /// which signatures can be forged
+    SmallOrder,
+}
+
+impl fmt::Display for KeyQuality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyQuality::Healthy => write!(f, "healthy (valid point, not of small order)"),
+            KeyQuality::AllZero => write!(f, "DEGENERATE (all-zero public key)"),
+            KeyQuality::SmallOrder => write!(f, "DEGENERATE (small-order public key)"),
+        }
+    }
+}
+
+/// Checks a public key for the degenerate cases a broken generator could
+/// produce. A key derived from a random secret is healthy with
+/// overwhelming probability, so this is purely reassurance.
+pub fn assess_key(verifying_key: &VerifyingKey) -> KeyQuality {
+    if verifying_key.as_bytes().iter().all(|&b| b == 0) {
+        KeyQuality::AllZero
+    } else if verifying_key.is_weak() {
+        KeyQuality::SmallOrder
+    } else {
+        KeyQuality::Healthy
+    }
+}
+
+/// Assesses the key in an OpenSSH Ed25519 public key line
+pub fn assess_ssh_public_key(ssh_key: &str) -> Result<KeyQuality> {
+    let bytes: [u8; 32] = decode_ssh_public_key(ssh_key)?
+        .try_into()
+        .map_err(|_| VanityError::InvalidFormat("public key is not 32 bytes".into()))?;
+    let verifying_key = VerifyingKey::from_bytes(&bytes).map_err(|e| {
+        VanityError::InvalidFormat(format!("public key is not a valid point: {}", e))
+    })?;
+    Ok(assess_key(&verifying_key))
+}
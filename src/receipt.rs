@@ -0,0 +1,106 @@
+// src/receipt.rs
+
+use crate::error::{Result, VanityError};
+use crate::ssh::public_key::{fingerprint_sha256, ssh_public_key_blob};
+use crate::thread_pool::{KeyMatch, ThreadPoolConfig};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where the seed of a deterministic run came from
+#[derive(Serialize, Clone)]
+pub struct SeedSource {
+    /// How the seed was provided: "file" or "number"
+    pub kind: String,
+    /// Path of the seed file, if any
+    pub path: Option<PathBuf>,
+}
+
+impl SeedSource {
+    /// Describes a seed read from a file
+    pub fn file(path: &Path) -> Self {
+        SeedSource {
+            kind: "file".into(),
+            path: Some(path.to_path_buf()),
+        }
+    }
+
+    /// Describes a seed given as a number with `--seed`
+    pub fn number() -> Self {
+        SeedSource {
+            kind: "number".into(),
+            path: None,
+        }
+    }
+}
+
+/// One match recorded in a receipt
+#[derive(Serialize)]
+pub struct ReceiptMatch {
+    /// Worker thread that generated the key
+    pub thread_id: usize,
+    /// Position of the key in that thread's sequence (1 = first key)
+    pub thread_attempt: u64,
+    pub public_key: String,
+    pub fingerprint: String,
+}
+
+/// A record of the parameters that produced a set of keys.
+///
+/// With a seeded run, the seed, thread id and per-thread attempt number are
+/// enough for a third party to regenerate the exact same key with
+/// `keygen::reproduce_openssh_key_pair` and compare fingerprints.
+#[derive(Serialize)]
+pub struct Receipt {
+    pub tool: String,
+    pub algorithm: String,
+    pub rng: String,
+    pub seed_source: Option<SeedSource>,
+    /// Hex-encoded 32-byte seed of the run
+    pub seed: Option<String>,
+    pub pattern: String,
+    pub case_sensitive: bool,
+    pub comment: Option<String>,
+    pub matches: Vec<ReceiptMatch>,
+}
+
+impl Receipt {
+    /// Starts a receipt for a search run with the given configuration
+    pub fn new(config: &ThreadPoolConfig, seed_source: Option<SeedSource>) -> Self {
+        Receipt {
+            tool: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            algorithm: config.algorithm.key_type().to_string(),
+            rng: if config.seed.is_some() {
+                "chacha20".into()
+            } else {
+                "chacha20-os-seeded".into()
+            },
+            seed_source,
+            seed: config.seed.map(hex::encode),
+            pattern: config.pattern.clone(),
+            case_sensitive: config.case_sensitive,
+            comment: config.comment.clone(),
+            matches: Vec::new(),
+        }
+    }
+
+    /// Records a match in the receipt
+    pub fn record(&mut self, key_match: &KeyMatch) -> Result<()> {
+        let blob = ssh_public_key_blob(&key_match.public_key)?;
+        self.matches.push(ReceiptMatch {
+            thread_id: key_match.thread_id,
+            thread_attempt: key_match.attempts,
+            public_key: key_match.public_key.clone(),
+            fingerprint: fingerprint_sha256(&blob),
+        });
+        Ok(())
+    }
+
+    /// Writes the receipt to a file as pretty-printed JSON
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| VanityError::EncodingError(e.to_string()))?;
+        fs::write(path, json + "\n")?;
+        Ok(())
+    }
+}
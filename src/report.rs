@@ -0,0 +1,545 @@
+// src/report.rs
+
+use crate::error::Result;
+use crate::search::{self, SearchEvent};
+use crate::state::SearchState;
+use crate::thread_pool::{KeyMatch, ThreadPoolConfig};
+use crate::{
+    estimated_attempts, fifo, format, json, matcher, output, prometheus, quality, receipt, ssh,
+    stats, verify, PerformanceMetrics, VanitySearch,
+};
+use chrono::Local;
+use crossbeam_channel::Receiver;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Options controlling what happens to each match besides printing it
+pub struct OutputOptions {
+    /// Save each matched key pair into this directory
+    pub output_dir: Option<PathBuf>,
+    /// Append each matched public key to this authorized_keys file
+    pub append_authorized_keys: Option<PathBuf>,
+    /// `authorized_keys` options put in front of each appended key
+    pub key_options: Option<String>,
+    /// Terminate written public key lines with a newline (default: true)
+    pub pub_trailing_newline: bool,
+    /// Print each matched public key as an Ansible `authorized_key` task
+    pub ansible: bool,
+    /// Print an `ssh-keygen -s` command for signing each saved public key
+    pub cert_template: bool,
+    /// Print each match as a line of JSON instead of the human-readable
+    /// report, and hide the progress spinner
+    pub json: bool,
+    /// Write NDJSON progress events to this FIFO, creating it if needed
+    pub progress_fifo: Option<PathBuf>,
+    /// Keep Prometheus metrics for the search in this file, rewritten on
+    /// every progress update
+    pub metrics_file: Option<PathBuf>,
+    /// Print only the matched public and private keys, for scripting
+    pub quiet: bool,
+    /// Never draw the progress display. It's also left out whenever stderr
+    /// isn't a terminal, so redirected output stays free of control codes.
+    pub no_progress: bool,
+    /// Print a sanity check of each matched public key
+    pub assess: bool,
+    /// Print the randomart of each match's SHA256 fingerprint
+    pub randomart: bool,
+    /// Check that each matched key pair signs and verifies a test message
+    /// before reporting it. Ed25519 only.
+    pub verify: bool,
+    /// Format of the private key in the report and saved files
+    pub private_format: ssh::PrivateKeyFormat,
+    /// Keep lifetime totals across runs in this file, continuing from the
+    /// totals already in it
+    pub state_file: Option<PathBuf>,
+    /// Write a JSON reproducibility receipt to this file when the search ends
+    pub receipt_file: Option<PathBuf>,
+    /// Where the seed of a deterministic run came from, for the receipt
+    pub seed_source: Option<receipt::SeedSource>,
+    /// Read patterns from stdin while searching, and switch the search to
+    /// each one typed, keeping the metrics so far
+    pub interactive: bool,
+    /// Print a snapshot of the metrics to stderr whenever this flag is
+    /// raised, and lower it again. The command-line tool raises it on
+    /// SIGUSR1.
+    pub stats_signal: Option<Arc<AtomicBool>>,
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        OutputOptions {
+            output_dir: None,
+            append_authorized_keys: None,
+            key_options: None,
+            pub_trailing_newline: true,
+            ansible: false,
+            cert_template: false,
+            json: false,
+            progress_fifo: None,
+            metrics_file: None,
+            quiet: false,
+            no_progress: false,
+            assess: false,
+            randomart: false,
+            verify: false,
+            private_format: ssh::PrivateKeyFormat::OpenSsh,
+            state_file: None,
+            receipt_file: None,
+            seed_source: None,
+            interactive: false,
+            stats_signal: None,
+        }
+    }
+}
+
+/// Continuously generates random ed25519 key pairs in OpenSSH format
+/// and matches the public key against a regex pattern.
+/// This is the multi-threaded version of the key generation function.
+///
+/// # Arguments
+///
+/// * `pattern` - The regex pattern to match against
+/// * `streaming` - Whether to continue after finding a match
+/// * `comment` - Optional comment to add to the SSH key
+/// * `case_sensitive` - Whether to perform case-sensitive matching
+/// * `threads` - Number of worker threads to use (default: number of CPU cores)
+///
+/// # Returns
+///
+/// Performance metrics for the operation
+///
+/// New code may prefer the `VanitySearch` builder, which this wraps, or
+/// `stream_with_handlers`, which runs the same search without printing.
+pub fn stream_openssh_keys_and_match_mt(
+    pattern: &str,
+    streaming: bool,
+    comment: Option<&str>,
+    case_sensitive: bool,
+    threads: Option<usize>,
+) -> Result<PerformanceMetrics> {
+    let mut search = VanitySearch::new(pattern)
+        .streaming(streaming)
+        .case_sensitive(case_sensitive);
+    if let Some(comment) = comment {
+        search = search.comment(comment);
+    }
+    // Without a thread count the builder uses one thread per CPU core
+    if let Some(thread_count) = threads {
+        search = search.threads(thread_count);
+    }
+
+    search.run()
+}
+
+/// Runs the multi-threaded search described by `config`, printing each match
+/// and handling it according to `output`.
+///
+/// # Returns
+///
+/// Performance metrics for the operation
+pub fn run_search(config: ThreadPoolConfig, output: &OutputOptions) -> Result<PerformanceMetrics> {
+    let thread_count = config.thread_count;
+
+    // Setup progress bar, with an ETA when the pattern's difficulty is known
+    let mut expected_attempts = estimated_attempts(&config);
+    let hide_progress =
+        output.json || output.quiet || output.no_progress || !io::stderr().is_terminal();
+    let mut pb = new_progress_bar(hide_progress, expected_attempts);
+    // Attempts made before the current bar was created
+    let mut bar_start: u64 = 0;
+
+    // In JSON and quiet mode stdout carries only the keys, so other messages
+    // go to stderr
+    let note = |message: String| {
+        if output.json || output.quiet {
+            eprintln!("{}", message);
+        } else {
+            println!("{}", message);
+        }
+    };
+
+    let start_time = Instant::now();
+
+    // Keep a copy of the search parameters for scoring matches
+    let mut search_config = config.clone();
+    let mut receipt = output
+        .receipt_file
+        .as_ref()
+        .map(|_| receipt::Receipt::new(&search_config, output.seed_source.clone()));
+
+    let mut progress_fifo = output
+        .progress_fifo
+        .as_deref()
+        .map(fifo::ProgressFifo::create)
+        .transpose()?;
+
+    // Written once up front, so an unwritable path fails before the search
+    let metrics_file = output.metrics_file.as_deref();
+    if let Some(path) = metrics_file {
+        prometheus::write_textfile(path, &PerformanceMetrics::new())?;
+    }
+
+    // Attempts and matches from earlier runs carry over, and the file is
+    // rewritten with the lifetime totals on every update
+    let state = output
+        .state_file
+        .as_deref()
+        .map(|path| SearchState::load(path).map(|prior| (path, prior)))
+        .transpose()?;
+    if let Some((path, prior)) = state {
+        prior.write(path)?;
+        if prior.total_attempts > 0 {
+            note(format!(
+                "Continuing from {} attempts recorded in {}",
+                prior.total_attempts,
+                path.display()
+            ));
+        }
+    }
+    let save_state = |metrics: &PerformanceMetrics| match state {
+        Some((path, prior)) => prior.with_run(metrics, start_time.elapsed()).write(path),
+        None => Ok(()),
+    };
+
+    // An interactive search switches to each pattern typed on stdin
+    let pattern_lines = if output.interactive {
+        note("Type a new pattern and press Enter to search for it instead".to_string());
+        spawn_line_reader()
+    } else {
+        crossbeam_channel::never()
+    };
+
+    // Index of the next key file written to the output directory
+    let mut next_file_index: u64 = 1;
+
+    let metrics = search::drive(config, pattern_lines, |event| {
+        match event {
+            SearchEvent::Match {
+                key_match,
+                metrics,
+                last,
+            } => {
+                let now = Local::now();
+
+                // Clear progress spinner when reporting a match
+                pb.finish_and_clear();
+
+                // A key that fails the self-test is a bug, so stop the search
+                if output.verify {
+                    verify::verify_signature(&key_match.private_key, &key_match.public_key)?;
+                }
+
+                // Everything below reports and saves the key in the chosen format
+                let key_match = match output.private_format {
+                    ssh::PrivateKeyFormat::OpenSsh => key_match,
+                    ssh::PrivateKeyFormat::Pkcs8 => KeyMatch {
+                        private_key: ssh::private_key::openssh_to_pkcs8_pem(
+                            &key_match.private_key,
+                        )?,
+                        ..key_match
+                    },
+                    ssh::PrivateKeyFormat::Ppk => KeyMatch {
+                        private_key: ssh::private_key::openssh_to_ppk(&key_match.private_key)?,
+                        ..key_match
+                    },
+                };
+
+                // Report the match
+                let score = stats::vanity_score(&key_match, &search_config);
+                if output.json {
+                    let timestamp = now.to_rfc3339();
+                    println!(
+                        "{}",
+                        json::to_line(&json::MatchRecord::new(&key_match, &timestamp, score))?
+                    );
+                } else if output.quiet {
+                    println!("{}", key_match.public_key);
+                    println!("{}", key_match.private_key);
+                } else {
+                    println!(
+                        "\n[{}] Match found after {} global attempts (thread {}, local {})!",
+                        now.format("%Y-%m-%d %H:%M:%S"),
+                        key_match.total_attempts,
+                        key_match.thread_id,
+                        key_match.attempts
+                    );
+                    println!("Public Key:  {}", key_match.public_key);
+                    let blob = ssh::public_key::ssh_public_key_blob(&key_match.public_key)?;
+                    println!(
+                        "Fingerprint: {}",
+                        ssh::public_key::fingerprint_sha256(&blob)
+                    );
+                    println!("Fingerprint: {}", ssh::public_key::fingerprint_md5(&blob));
+                    if !search_config.extra_patterns.is_empty() {
+                        println!("Pattern:     {}", key_match.matched_pattern);
+                    }
+                    if search_config.prefer_exact {
+                        println!(
+                            "Exact case:  {}",
+                            if key_match.exact_case { "yes" } else { "no" }
+                        );
+                    }
+                    // Show the text the pattern was matched against
+                    if search_config.encoding != matcher::Encoding::Base64 {
+                        if let Ok(raw) =
+                            ssh::public_key::decode_ssh_public_key(&key_match.public_key)
+                        {
+                            let label = format!("{:?}:", search_config.encoding);
+                            println!(
+                                "{:<13}{}",
+                                label,
+                                matcher::encode_key(&raw, search_config.encoding)?
+                            );
+                        }
+                    }
+                    if output.randomart {
+                        println!("{}", ssh::ssh_key_randomart(&key_match.public_key)?);
+                    }
+                    println!("Private Key:\n{}", key_match.private_key);
+                    if output.private_format == ssh::PrivateKeyFormat::Pkcs8 {
+                        let raw = ssh::public_key::decode_ssh_public_key(&key_match.public_key)?;
+                        println!(
+                            "Public Key (SPKI):\n{}",
+                            ssh::public_key::encode_spki_pem(&raw)?
+                        );
+                    }
+                    if !key_match.captures.is_empty() {
+                        let captures: Vec<String> = key_match
+                            .captures
+                            .iter()
+                            .map(|(name, value)| format!("{}={}", name, value))
+                            .collect();
+                        println!("Captures: {}", captures.join(" "));
+                    }
+                    println!("Vanity score: {:.1}", score);
+                    if output.assess {
+                        println!(
+                            "Key quality: {}",
+                            quality::assess_ssh_public_key(&key_match.public_key)?
+                        );
+                    }
+                    println!("Performance: {}", metrics);
+                }
+
+                if output.ansible {
+                    note(format!(
+                        "Ansible task:\n{}",
+                        format::ansible_authorized_key(&key_match.public_key)?
+                    ));
+                }
+
+                if let Some(ref dir) = output.output_dir {
+                    let (index, private_path, public_path) = output::save_key_pair(
+                        dir,
+                        next_file_index,
+                        &key_match,
+                        output.pub_trailing_newline,
+                    )?;
+                    next_file_index = index + 1;
+                    note(format!(
+                        "Saved to {} and {}",
+                        private_path.display(),
+                        public_path.display()
+                    ));
+                    if output.cert_template {
+                        note(format!(
+                            "Sign with: {}",
+                            format::cert_template(&public_path)
+                        ));
+                    }
+                }
+
+                if let Some(ref path) = output.append_authorized_keys {
+                    ssh::public_key::append_authorized_key_with_options(
+                        path,
+                        &key_match.public_key,
+                        output.key_options.as_deref(),
+                        output.pub_trailing_newline,
+                    )?;
+                    note(format!("Public key appended to {}", path.display()));
+                }
+
+                if let Some(ref mut receipt) = receipt {
+                    receipt.record(&key_match)?;
+                }
+
+                if last {
+                    return Ok(());
+                }
+
+                // In streaming mode, we need to completely recreate the progress bar
+                // rather than just reinitializing it
+                pb = new_progress_bar(hide_progress, expected_attempts);
+                bar_start = metrics.attempts;
+
+                // Add a newline before continuing to ensure progress bar appears on its own line
+                if !output.json && !output.quiet {
+                    println!("\nContinuing search for more matches...");
+                }
+                if output.interactive {
+                    note("Type a new pattern and press Enter to search for it instead".to_string());
+                }
+
+                // Show the progress right away, with a message that indicates
+                // we're continuing the search
+                pb.set_message(progress_message(metrics, thread_count));
+                set_bar_position(&pb, metrics.attempts - bar_start);
+            }
+
+            SearchEvent::Progress(metrics) => {
+                pb.set_message(progress_message(metrics, thread_count));
+                set_bar_position(&pb, metrics.attempts - bar_start);
+                if let Some(ref mut fifo) = progress_fifo {
+                    fifo.send(&json::to_line(&json::ProgressEvent::from(metrics))?);
+                }
+                if let Some(path) = metrics_file {
+                    prometheus::write_textfile(path, metrics)?;
+                }
+                save_state(metrics)?;
+
+                if output
+                    .stats_signal
+                    .as_ref()
+                    .is_some_and(|flag| flag.swap(false, Ordering::Relaxed))
+                {
+                    pb.suspend(|| {
+                        eprintln!("Stats: {}", metrics);
+                        eprintln!("Per thread: {}", metrics.per_thread_breakdown());
+                    });
+                }
+            }
+
+            SearchEvent::Switched { config, metrics } => {
+                search_config = config.clone();
+                pb.finish_and_clear();
+                expected_attempts = estimated_attempts(&search_config);
+                pb = new_progress_bar(hide_progress, expected_attempts);
+                bar_start = metrics.attempts;
+                note(format!("Now searching for {}", search_config.pattern));
+            }
+
+            SearchEvent::InvalidPattern { pattern, error } => {
+                note(format!("Invalid pattern {:?}: {}", pattern, error));
+            }
+
+            SearchEvent::TimedOut => {
+                pb.finish_and_clear();
+                note("\nTimeout reached, stopping search".to_string());
+            }
+        }
+        Ok(())
+    })?;
+
+    pb.finish_and_clear();
+    if let Some(path) = metrics_file {
+        prometheus::write_textfile(path, &metrics)?;
+    }
+    save_state(&metrics)?;
+    if let Some((_, prior)) = state {
+        let lifetime = prior.with_run(&metrics, start_time.elapsed());
+        note(format!(
+            "Lifetime: {} attempts, {} matches",
+            lifetime.total_attempts, lifetime.matches_found
+        ));
+    }
+
+    if let (Some(receipt), Some(path)) = (receipt, output.receipt_file.as_ref()) {
+        receipt.write(path)?;
+    }
+
+    Ok(metrics)
+}
+
+/// Reads lines from stdin on a thread of their own, so the search can wait
+/// for them alongside its channels. The channel closes at the end of stdin.
+fn spawn_line_reader() -> Receiver<String> {
+    let (sender, receiver) = crossbeam_channel::unbounded();
+    thread::spawn(move || {
+        for line in io::stdin().lines() {
+            let Ok(line) = line else { break };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// One-line status for the progress spinner
+fn progress_message(metrics: &PerformanceMetrics, thread_count: usize) -> String {
+    format!(
+        "Attempts: {} | Matches: {} | Duration: {:.2}s | Speed: {:.2} keys/sec avg, {:.2} now (Threads: {})",
+        metrics.attempts,
+        metrics.matches_found,
+        metrics.duration.as_secs_f64(),
+        metrics.keys_per_second,
+        metrics.instantaneous_rate(),
+        thread_count
+    )
+}
+
+/// Creates the progress display shown while searching: a bar with an ETA
+/// towards `expected_attempts` when it's known, otherwise a spinner, or a
+/// hidden one
+fn new_progress_bar(hidden: bool, expected_attempts: Option<u64>) -> ProgressBar {
+    if hidden {
+        return ProgressBar::hidden();
+    }
+
+    if let Some(expected) = expected_attempts {
+        let pb = ProgressBar::new(expected);
+        pb.set_style(
+            ProgressStyle::default_bar()
+                .template("{spinner:.green} [{bar:30.cyan/blue}] {percent}% ETA ~{eta} {msg}")
+                .unwrap()
+                .progress_chars("=> "),
+        );
+        pb.enable_steady_tick(Duration::from_millis(100));
+        return pb;
+    }
+
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(
+        ProgressStyle::default_spinner()
+            .template("{spinner:.green} {msg}")
+            .unwrap(),
+    );
+    pb.enable_steady_tick(Duration::from_millis(100));
+    pb
+}
+
+/// Moves a bar to `attempts`, stopping at its end since a search can take
+/// longer than expected. Spinners have no length and are left alone.
+fn set_bar_position(pb: &ProgressBar, attempts: u64) {
+    if let Some(length) = pb.length() {
+        pb.set_position(attempts.min(length));
+    }
+}
+
+// Keep the original single-threaded function for backward compatibility.
+// It runs a pool with one worker thread; see `find_key_inline` for a search
+// on the calling thread.
+pub fn stream_openssh_keys_and_match(
+    pattern: &str,
+    streaming: bool,
+    comment: Option<&str>,
+    case_sensitive: bool,
+) -> Result<PerformanceMetrics> {
+    // By default, use the multi-threaded version with 1 thread
+    stream_openssh_keys_and_match_mt(pattern, streaming, comment, case_sensitive, Some(1))
+}
+
+// Original stream_keys_and_match for backward compatibility
+pub fn stream_keys_and_match(
+    pattern: &str,
+    streaming: bool,
+    case_sensitive: bool,
+) -> Result<PerformanceMetrics> {
+    // Call the multi-threaded version with 1 thread
+    stream_openssh_keys_and_match_mt(pattern, streaming, None, case_sensitive, Some(1))
+}
@@ -0,0 +1,335 @@
+// src/search.rs
+use crate::error::{Result, VanityError};
+use crate::keygen::KeyAlgorithm;
+use crate::matcher;
+#[cfg(feature = "cli")]
+use crate::report::{run_search, OutputOptions};
+use crate::thread_pool::{run_thread_pool, KeyMatch, ThreadPoolConfig};
+use crate::{ActiveClock, PerformanceMetrics};
+use crossbeam_channel::{select, Receiver};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Builder for a vanity key search, for embedding the generator in other
+/// programs.
+///
+/// ```no_run
+/// use vanityssh_rust::VanitySearch;
+///
+/// let metrics = VanitySearch::new("cafe")
+///     .threads(4)
+///     .comment("me@example.com")
+///     .run_with_callback(|key_match| println!("{}", key_match.public_key))?;
+/// # Ok::<(), vanityssh_rust::error::VanityError>(())
+/// ```
+pub struct VanitySearch {
+    config: ThreadPoolConfig,
+}
+
+impl VanitySearch {
+    /// Starts a search for `pattern` with the default options: one match,
+    /// case-insensitive, one thread per CPU core
+    pub fn new(pattern: impl Into<String>) -> Self {
+        VanitySearch {
+            config: ThreadPoolConfig {
+                pattern: pattern.into(),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Number of worker threads
+    pub fn threads(mut self, thread_count: usize) -> Self {
+        self.config.thread_count = thread_count;
+        self
+    }
+
+    /// Keep searching after the first match
+    pub fn streaming(mut self, streaming: bool) -> Self {
+        self.config.streaming = streaming;
+        self
+    }
+
+    /// Comment stored in the generated keys
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.config.comment = Some(comment.into());
+        self
+    }
+
+    /// Match the pattern case-sensitively
+    pub fn case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.config.case_sensitive = case_sensitive;
+        self
+    }
+
+    /// Stop after generating about this many keys
+    pub fn max_attempts(mut self, max_attempts: u64) -> Self {
+        self.config.max_attempts = Some(max_attempts);
+        self
+    }
+
+    /// Keep searching until `count` matches have been found
+    pub fn count(mut self, count: u64) -> Self {
+        self.config.streaming = true;
+        self.config.max_matches = Some(count);
+        self
+    }
+
+    /// Generate at most about this many keys per second, across all threads
+    pub fn max_rate(mut self, keys_per_second: f64) -> Self {
+        self.config.max_rate = Some(keys_per_second);
+        self
+    }
+
+    /// Stop after this much wall-clock time
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = Some(timeout);
+        self
+    }
+
+    /// Stop the search as soon as `token` is set, e.g. from a "Stop" button
+    /// on another thread. The run methods then return the metrics so far.
+    /// The search sets the token itself when it ends, so a token can't be
+    /// reused for the next search.
+    pub fn cancel_token(mut self, token: Arc<AtomicBool>) -> Self {
+        self.config.terminate = Some(token);
+        self
+    }
+
+    /// Type of key to generate; Ed25519 unless set
+    pub fn algorithm(mut self, algorithm: KeyAlgorithm) -> Self {
+        self.config.algorithm = algorithm;
+        self
+    }
+
+    /// Runs the search, printing each match and a progress spinner like the
+    /// command-line tool does
+    #[cfg(feature = "cli")]
+    pub fn run(self) -> Result<PerformanceMetrics> {
+        run_search(self.config, &OutputOptions::default())
+    }
+
+    /// Runs the search without printing anything, passing each match to
+    /// `on_match` on the calling thread
+    pub fn run_with_callback<F: FnMut(KeyMatch)>(
+        self,
+        mut on_match: F,
+    ) -> Result<PerformanceMetrics> {
+        drive(self.config, crossbeam_channel::never(), |event| {
+            if let SearchEvent::Match { key_match, .. } = event {
+                on_match(key_match);
+            }
+            Ok(())
+        })
+    }
+}
+
+/// How often `stream_with_handlers` reports progress
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Runs the search described by `config` without printing anything or
+/// drawing a progress display, for GUIs and other front ends.
+///
+/// Both handlers run on the calling thread: `on_match` for every match, and
+/// `on_progress` about twice a second with the metrics so far. Without
+/// `config.streaming` the search ends after the first match, and with
+/// `config.max_matches` after that many.
+pub fn stream_with_handlers<M, P>(
+    config: ThreadPoolConfig,
+    mut on_match: M,
+    mut on_progress: P,
+) -> Result<PerformanceMetrics>
+where
+    M: FnMut(&KeyMatch),
+    P: FnMut(&PerformanceMetrics),
+{
+    drive(config, crossbeam_channel::never(), |event| {
+        match event {
+            SearchEvent::Match { key_match, .. } => on_match(&key_match),
+            SearchEvent::Progress(metrics) => on_progress(metrics),
+            _ => {}
+        }
+        Ok(())
+    })
+}
+
+/// What `drive` tells its handler about a running search. Only the
+/// command-line report reads most of it.
+#[cfg_attr(not(feature = "cli"), allow(dead_code))]
+pub(crate) enum SearchEvent<'a> {
+    /// A key matched. `metrics` already counts it, and `last` says the
+    /// search ends with it.
+    Match {
+        key_match: KeyMatch,
+        metrics: &'a PerformanceMetrics,
+        last: bool,
+    },
+    /// About twice a second, with the metrics so far
+    Progress(&'a PerformanceMetrics),
+    /// The search now looks for the pattern in `config`, typed on the
+    /// pattern channel
+    Switched {
+        config: &'a ThreadPoolConfig,
+        metrics: &'a PerformanceMetrics,
+    },
+    /// A line from the pattern channel didn't compile and was skipped
+    InvalidPattern {
+        pattern: &'a str,
+        error: VanityError,
+    },
+    /// The search ran out of time and is stopping
+    TimedOut,
+}
+
+/// The receive loop behind every search: `stream_with_handlers`,
+/// `VanitySearch::run_with_callback` and the command-line `run_search`.
+///
+/// Each line received on `patterns` replaces the search's patterns, with
+/// the metrics carrying on; pass `crossbeam_channel::never()` to keep them.
+/// An error from `handle_event` ends the search with that error.
+pub(crate) fn drive<H>(
+    config: ThreadPoolConfig,
+    mut patterns: Receiver<String>,
+    mut handle_event: H,
+) -> Result<PerformanceMetrics>
+where
+    H: FnMut(SearchEvent<'_>) -> Result<()>,
+{
+    let streaming = config.streaming;
+    let timeout = config.timeout;
+    let max_matches = config.max_matches;
+    let start = Instant::now();
+    let thread_count = config.thread_count;
+    let mut search_config = config.clone();
+    let mut handle = run_thread_pool(config)?;
+
+    // The loop below wakes up at least every 100ms, so a much longer pause
+    // means the process was suspended, and is left out of the rate
+    let mut clock = ActiveClock::new(start, PROGRESS_INTERVAL * 4);
+    let mut last_progress = start;
+
+    // A pattern switch replaces the pool. These count what the replaced
+    // pools did, so the metrics carry on across patterns.
+    let mut attempts_before: u64 = 0;
+    let mut near_misses_before: u64 = 0;
+
+    let mut metrics = PerformanceMetrics::new();
+    metrics.per_thread = vec![0; thread_count];
+    let mut matches_found: u64 = 0;
+    loop {
+        if timeout.is_some_and(|timeout| start.elapsed() >= timeout) {
+            handle_event(SearchEvent::TimedOut)?;
+            break;
+        }
+
+        // A new pattern, switched to below once the select no longer
+        // borrows the pool's receivers
+        let mut new_pattern = None;
+
+        select! {
+            recv(handle.match_receiver) -> msg => match msg {
+                Ok(key_match) => {
+                    let total_attempts = attempts_before + handle.attempts();
+                    let key_match = KeyMatch {
+                        total_attempts: attempts_before + key_match.total_attempts,
+                        ..key_match
+                    };
+                    matches_found += 1;
+                    let elapsed = clock.sample(Instant::now());
+                    metrics.update(total_attempts, matches_found, elapsed);
+                    log::debug!(
+                        "received match {} from thread {} at {} attempts after {:.2?}",
+                        matches_found,
+                        key_match.thread_id,
+                        total_attempts,
+                        elapsed
+                    );
+
+                    let last = !streaming || max_matches.is_some_and(|max| matches_found >= max);
+                    handle_event(SearchEvent::Match { key_match, metrics: &metrics, last })?;
+                    if last {
+                        break;
+                    }
+                }
+                // Every worker has exited
+                Err(_) => break,
+            },
+            recv(handle.status_receiver) -> msg => {
+                if let Ok(status) = msg {
+                    metrics.record_thread_attempts(status.thread_id, status.attempts);
+                    log::trace!(
+                        "status from thread {}: +{} attempts, {} in total",
+                        status.thread_id,
+                        status.attempts,
+                        attempts_before + handle.attempts()
+                    );
+                }
+            },
+            recv(patterns) -> msg => match msg {
+                Ok(line) => {
+                    let pattern = line.trim();
+                    if pattern.is_empty() {
+                        // Nothing to switch to
+                    } else if let Err(error) =
+                        matcher::compile_pattern(pattern, search_config.case_sensitive)
+                    {
+                        handle_event(SearchEvent::InvalidPattern { pattern, error })?;
+                    } else {
+                        new_pattern = Some(pattern.to_string());
+                    }
+                }
+                // The sender is gone, so keep the current pattern for good
+                Err(_) => patterns = crossbeam_channel::never(),
+            },
+            default(Duration::from_millis(100)) => {}
+        }
+
+        if let Some(pattern) = new_pattern {
+            // A stop already asked for, e.g. by Ctrl-C or a spent attempt
+            // budget, ends the search instead
+            let terminate = handle.terminate_flag();
+            if terminate.load(Ordering::Relaxed) {
+                break;
+            }
+            near_misses_before += handle.near_misses();
+            attempts_before += handle.shutdown();
+            // Shutting down raised the shared flag the next pool stops on
+            terminate.store(false, Ordering::Relaxed);
+
+            // The new pattern replaces every pattern the search had, and
+            // the new pool gets what's left of the attempt budget
+            search_config.pattern = pattern;
+            search_config.extra_patterns.clear();
+            let mut pool_config = search_config.clone();
+            pool_config.max_attempts = search_config
+                .max_attempts
+                .map(|max| max.saturating_sub(attempts_before));
+            handle = run_thread_pool(pool_config)?;
+
+            metrics.update(attempts_before, matches_found, clock.sample(Instant::now()));
+            handle_event(SearchEvent::Switched {
+                config: &search_config,
+                metrics: &metrics,
+            })?;
+        }
+
+        let now = Instant::now();
+        let elapsed = clock.sample(now);
+        if now.duration_since(last_progress) >= PROGRESS_INTERVAL {
+            metrics.update(attempts_before + handle.attempts(), matches_found, elapsed);
+            handle_event(SearchEvent::Progress(&metrics))?;
+            last_progress = now;
+        }
+    }
+
+    // Count per-thread progress that was reported but not yet received
+    for status in handle.status_receiver.try_iter() {
+        metrics.record_thread_attempts(status.thread_id, status.attempts);
+    }
+    metrics.near_misses = near_misses_before + handle.near_misses();
+    let attempts = attempts_before + handle.shutdown();
+
+    metrics.update(attempts, matches_found, clock.sample(Instant::now()));
+    Ok(metrics)
+}
@@ -0,0 +1,50 @@
+// src/signals.rs
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+/// A signal set holding just `signals`
+pub fn signal_set(signals: &[libc::c_int]) -> libc::sigset_t {
+    // SAFETY: plain libc signal-set manipulation on a local sigset_t
+    unsafe {
+        let mut set: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut set);
+        for &signal in signals {
+            libc::sigaddset(&mut set, signal);
+        }
+        set
+    }
+}
+
+/// Blocks `signals` in the calling thread. Called from `main` before any
+/// thread is spawned, every thread inherits the mask, so the signals only
+/// reach the watcher threads that `sigwait` for them instead of killing the
+/// process with their default action.
+pub fn block(signals: &[libc::c_int]) -> io::Result<()> {
+    let set = signal_set(signals);
+    // SAFETY: `set` is a valid, initialised signal set
+    if unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, &set, std::ptr::null_mut()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Starts a thread that raises the returned flag each time SIGUSR1 arrives,
+/// for the search loop to print a stats snapshot. SIGUSR1 must already be
+/// blocked with `block`.
+pub fn spawn_stats_watcher() -> Arc<AtomicBool> {
+    let set = signal_set(&[libc::SIGUSR1]);
+    let requested = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&requested);
+    thread::spawn(move || loop {
+        let mut signal = 0;
+        // SAFETY: `set` is a valid, initialised signal set
+        if unsafe { libc::sigwait(&set, &mut signal) } == 0 {
+            flag.store(true, Ordering::Relaxed);
+        }
+    });
+
+    requested
+}
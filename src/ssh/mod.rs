@@ -3,16 +3,108 @@
 
 pub mod private_key;
 pub mod public_key;
+pub mod randomart;
+pub mod rsa_key;
+
+use crate::error::{Result, VanityError};
+use std::fmt;
+use std::str::FromStr;
 
 // Re-export important functions for easier access
-pub use private_key::encode_ssh_private_key;
-pub use public_key::encode_ssh_public_key;
+pub use private_key::{
+    encode_pkcs8_pem, encode_ppk, encode_ssh_private_key, encode_ssh_private_key_with,
+    encode_ssh_private_key_wrapped, PrivateKeyFormat,
+};
+pub use public_key::{
+    encode_ssh_public_key, format_public_key_line, parse_public_key_line, validate_comment,
+    validate_key_options, PublicKeyLine,
+};
+pub use randomart::{randomart, ssh_key_randomart};
+pub use rsa_key::{encode_ssh_rsa_private_key_with, encode_ssh_rsa_public_key};
+
+/// A public key type OpenSSH names at the start of key lines and blobs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyType {
+    Ed25519,
+    /// An Ed25519 key held on a FIDO/U2F security key. These are read but
+    /// never generated, since the private half stays on the device.
+    SkEd25519,
+    Rsa,
+}
+
+impl KeyType {
+    /// Every key type we know about
+    pub const ALL: [KeyType; 3] = [KeyType::Ed25519, KeyType::SkEd25519, KeyType::Rsa];
+
+    /// The key type string, e.g. `ssh-ed25519`
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            KeyType::Ed25519 => "ssh-ed25519",
+            KeyType::SkEd25519 => "sk-ssh-ed25519@openssh.com",
+            KeyType::Rsa => "ssh-rsa",
+        }
+    }
+}
+
+impl fmt::Display for KeyType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for KeyType {
+    type Err = VanityError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        KeyType::ALL
+            .into_iter()
+            .find(|key_type| key_type.as_str() == s)
+            .ok_or_else(|| {
+                let known: Vec<&str> = KeyType::ALL.iter().map(|t| t.as_str()).collect();
+                VanityError::InvalidFormat(format!(
+                    "Expected key type {}, got {}",
+                    known.join(", "),
+                    s
+                ))
+            })
+    }
+}
 
 /// The key type string for Ed25519 SSH keys
-pub const ED25519_KEY_TYPE: &str = "ssh-ed25519";
+pub const ED25519_KEY_TYPE: &str = KeyType::Ed25519.as_str();
+
+/// The key type string for RSA SSH keys
+pub const RSA_KEY_TYPE: &str = KeyType::Rsa.as_str();
+
+/// Converts the length of a field in a key blob to its 4-byte length
+/// prefix, failing for fields longer than `u32::MAX` bytes instead of
+/// silently writing a truncated length
+pub fn length_prefix(len: usize) -> Result<u32> {
+    u32::try_from(len).map_err(|_| {
+        VanityError::EncodingError(format!(
+            "Field of {} bytes is too long for a key blob (at most {} bytes)",
+            len,
+            u32::MAX
+        ))
+    })
+}
+
+/// The base64 characters every Ed25519 public key body starts with.
+/// They encode the fixed key type and length prefix of the key blob.
+pub const ED25519_BODY_PREFIX: &str = "AAAAC3NzaC1lZDI1NTE5AAAAI";
 
 /// The OpenSSH magic header bytes
 pub const OPENSSH_MAGIC_BYTES: &[u8] = b"openssh-key-v1\0";
 
-/// The ED25519 comment to use (can be customized later)
+/// Line length of the base64 inside an OpenSSH private key, as written by
+/// `ssh-keygen`
+pub const OPENSSH_WRAP_WIDTH: usize = 70;
+
+/// Line length of the base64 inside a PuTTY `.ppk` file
+pub const PPK_LINE_WIDTH: usize = 64;
+
+/// Longest line length accepted by `encode_ssh_private_key_wrapped`
+pub const MAX_WRAP_WIDTH: usize = 200;
+
+/// The comment stored in the private key when none is given
 pub const DEFAULT_COMMENT: &str = "vanityssh-key";
@@ -1,56 +1,332 @@
 // src/ssh/public_key.rs
 // Created: 2025-04-22 13:35:37 by kengggg
 
-use super::ED25519_KEY_TYPE;
+use super::{length_prefix, KeyType, ED25519_KEY_TYPE};
 use crate::error::{Result, VanityError};
+use crate::keygen::KeyAlgorithm;
 use base64::{engine::general_purpose, Engine};
 use byteorder::{BigEndian, WriteBytesExt};
+use ed25519_dalek::pkcs8::{spki::der::pem::LineEnding, EncodePublicKey};
+use ed25519_dalek::VerifyingKey;
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Checks that a key comment can be stored safely.
+///
+/// A newline would split the public key line in two, corrupting
+/// `authorized_keys`, and other control characters such as NUL or tab are
+/// mangled by tools that read the comment back, so all of them are rejected.
+pub fn validate_comment(comment: &str) -> Result<()> {
+    if let Some(c) = comment.chars().find(|c| c.is_control()) {
+        return Err(VanityError::InvalidFormat(format!(
+            "Comment contains a control character: {:?}",
+            c
+        )));
+    }
+    Ok(())
+}
+
+/// Checks an `authorized_keys` options string such as
+/// `no-port-forwarding,from="10.0.0.0/8"` before it is put in front of a key.
+///
+/// Like a comment it must stay on one line, so control characters are
+/// rejected. It also can't be blank, contain spaces outside double quotes
+/// or leave a double quote open, any of which would make sshd misread the
+/// line.
+pub fn validate_key_options(options: &str) -> Result<()> {
+    if let Some(c) = options.chars().find(|c| c.is_control()) {
+        return Err(VanityError::InvalidFormat(format!(
+            "Key options contain a control character: {:?}",
+            c
+        )));
+    }
+    if options.trim().is_empty() {
+        return Err(VanityError::InvalidFormat("Key options are empty".into()));
+    }
+
+    // sshd allows \" inside a quoted value
+    let mut in_quotes = false;
+    let mut chars = options.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '\\' if in_quotes => {
+                chars.next();
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                return Err(VanityError::InvalidFormat(
+                    "Key options can only contain spaces inside double quotes".into(),
+                ));
+            }
+            _ => {}
+        }
+    }
+    if in_quotes {
+        return Err(VanityError::InvalidFormat(
+            "Key options have an unterminated double quote".into(),
+        ));
+    }
+    Ok(())
+}
 
 /// Encodes an Ed25519 public key in OpenSSH format.
 /// Returns a string in the format "ssh-ed25519 BASE64ENCODED_KEY [comment]"
 pub fn encode_ssh_public_key(public_key: &[u8], comment: Option<&str>) -> Result<String> {
+    let key_type = KeyAlgorithm::Ed25519.key_type();
+
     // Create the binary blob that will be base64 encoded
     let mut blob = Vec::new();
 
     // Add the key type string with its length prefix
-    write_length_prefixed_string(&mut blob, ED25519_KEY_TYPE)?;
+    write_length_prefixed_string(&mut blob, key_type.as_str())?;
 
     // Add the public key bytes with length prefix
     write_length_prefixed_bytes(&mut blob, public_key)?;
 
-    // Base64 encode the binary blob
-    let encoded = general_purpose::STANDARD.encode(&blob);
+    format_public_key_line(key_type, &blob, comment)
+}
+
+/// Formats a public key blob as an OpenSSH public key line,
+/// "KEY_TYPE BASE64ENCODED_BLOB [comment]"
+pub fn format_public_key_line(
+    key_type: KeyType,
+    blob: &[u8],
+    comment: Option<&str>,
+) -> Result<String> {
+    if let Some(comment_str) = comment {
+        validate_comment(comment_str)?;
+    }
+
+    let encoded = general_purpose::STANDARD.encode(blob);
 
-    // Format the final SSH public key string
     let ssh_key = if let Some(comment_str) = comment {
-        format!("{} {} {}", ED25519_KEY_TYPE, encoded, comment_str)
+        format!("{} {} {}", key_type, encoded, comment_str)
     } else {
-        format!("{} {}", ED25519_KEY_TYPE, encoded)
+        format!("{} {}", key_type, encoded)
     };
 
     Ok(ssh_key)
 }
 
-/// Extracts the base64-encoded portion from an SSH public key string.
-pub fn extract_ssh_key_data(ssh_key: &str) -> Result<String> {
-    let parts: Vec<&str> = ssh_key.split_whitespace().collect();
+/// Appends the base64 body of an Ed25519 public key's OpenSSH line to `out`,
+/// i.e. what `extract_ssh_key_data` returns for `encode_ssh_public_key`.
+///
+/// The blob is assembled on the stack, so nothing is allocated once `out`
+/// has room, which lets a worker reuse one buffer for every candidate key.
+pub fn encode_ssh_public_key_body(public_key: &[u8; 32], out: &mut String) {
+    const TYPE_LEN: usize = ED25519_KEY_TYPE.len();
+
+    let mut blob = [0u8; 4 + TYPE_LEN + 4 + 32];
+    blob[..4].copy_from_slice(&(TYPE_LEN as u32).to_be_bytes());
+    blob[4..4 + TYPE_LEN].copy_from_slice(ED25519_KEY_TYPE.as_bytes());
+    blob[4 + TYPE_LEN..8 + TYPE_LEN].copy_from_slice(&32u32.to_be_bytes());
+    blob[8 + TYPE_LEN..].copy_from_slice(public_key);
+
+    general_purpose::STANDARD.encode_string(blob, out);
+}
+
+/// The fields of an OpenSSH public key line, borrowed from the line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicKeyLine<'a> {
+    /// The key type, one of the `KeyType` strings
+    pub key_type: &'a str,
+    /// The base64-encoded key blob
+    pub body: &'a str,
+    /// Everything after the body, with its own inner spacing kept, or
+    /// `None` if there is nothing there
+    pub comment: Option<&'a str>,
+}
+
+/// Splits an OpenSSH public key line into its key type, base64 body and
+/// comment.
+///
+/// The fields may be separated by any run of spaces or tabs, and surrounding
+/// whitespace is ignored. Fails unless the line has at least a key type and a
+/// body, and the key type is one of `KeyType::ALL`.
+pub fn parse_public_key_line(ssh_key: &str) -> Result<PublicKeyLine<'_>> {
+    let (key_type, rest) = split_field(ssh_key.trim());
+    let (body, comment) = split_field(rest);
 
-    if parts.len() < 2 {
+    if body.is_empty() {
         return Err(VanityError::InvalidFormat(
             "Invalid SSH public key format".into(),
         ));
     }
 
-    // Ensure key type is correct
-    if parts[0] != ED25519_KEY_TYPE {
+    key_type.parse::<KeyType>()?;
+
+    Ok(PublicKeyLine {
+        key_type,
+        body,
+        comment: (!comment.is_empty()).then_some(comment),
+    })
+}
+
+/// Splits off the first whitespace-separated field of `text`, which must not
+/// start with whitespace, and returns it with the rest, leading whitespace
+/// removed
+fn split_field(text: &str) -> (&str, &str) {
+    match text.split_once(char::is_whitespace) {
+        Some((field, rest)) => (field, rest.trim_start()),
+        None => (text, ""),
+    }
+}
+
+/// Extracts the base64-encoded portion from an SSH public key string.
+pub fn extract_ssh_key_data(ssh_key: &str) -> Result<String> {
+    parse_public_key_line(ssh_key).map(|line| line.body.to_string())
+}
+
+/// Returns the binary key blob encoded in an OpenSSH public key line.
+pub fn ssh_public_key_blob(ssh_key: &str) -> Result<Vec<u8>> {
+    let encoded = extract_ssh_key_data(ssh_key)?;
+    general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|e| VanityError::EncodingError(e.to_string()))
+}
+
+/// Computes the OpenSSH SHA256 fingerprint of a public key blob,
+/// in the `SHA256:<base64 without padding>` form shown by `ssh-keygen -l`.
+pub fn fingerprint_sha256(blob: &[u8]) -> String {
+    let digest = Sha256::digest(blob);
+    format!("SHA256:{}", general_purpose::STANDARD_NO_PAD.encode(digest))
+}
+
+/// Computes the legacy MD5 fingerprint of a public key blob, in the
+/// `MD5:xx:xx:...` form shown by `ssh-keygen -l -E md5`.
+pub fn fingerprint_md5(blob: &[u8]) -> String {
+    let digest = Md5::digest(blob);
+    let hex: Vec<String> = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("MD5:{}", hex.join(":"))
+}
+
+/// Decodes an OpenSSH Ed25519 public key line into the raw 32-byte key.
+pub fn decode_ssh_public_key(ssh_key: &str) -> Result<Vec<u8>> {
+    let blob = ssh_public_key_blob(ssh_key)?;
+
+    // The blob is a length-prefixed key type followed by the length-prefixed key
+    let mut rest = blob.as_slice();
+    let key_type = take_length_prefixed(&mut rest)?;
+    if key_type != ED25519_KEY_TYPE.as_bytes() {
         return Err(VanityError::InvalidFormat(format!(
-            "Expected key type {}, got {}",
-            ED25519_KEY_TYPE, parts[0]
+            "Expected key type {} in key blob",
+            ED25519_KEY_TYPE
         )));
     }
+    let key = take_length_prefixed(&mut rest)?;
+    if key.len() != 32 || !rest.is_empty() {
+        return Err(VanityError::InvalidFormat(
+            "Invalid Ed25519 public key blob".into(),
+        ));
+    }
+
+    Ok(key.to_vec())
+}
+
+/// Encodes a raw 32-byte Ed25519 public key as SubjectPublicKeyInfo PEM
+/// (`-----BEGIN PUBLIC KEY-----`), the counterpart of PKCS#8 private keys
+pub fn encode_spki_pem(public_key: &[u8]) -> Result<String> {
+    let bytes: &[u8; 32] = public_key.try_into().map_err(|_| {
+        VanityError::InvalidFormat(format!(
+            "Expected a 32 byte Ed25519 public key, got {} bytes",
+            public_key.len()
+        ))
+    })?;
+    let verifying_key =
+        VerifyingKey::from_bytes(bytes).map_err(|e| VanityError::InvalidFormat(e.to_string()))?;
+    let pem = verifying_key
+        .to_public_key_pem(LineEnding::LF)
+        .map_err(|e| VanityError::EncodingError(e.to_string()))?;
+    Ok(pem.trim_end().to_string())
+}
+
+/// Helper function to split a length-prefixed field off the front of a slice
+pub(super) fn take_length_prefixed<'a>(buffer: &mut &'a [u8]) -> Result<&'a [u8]> {
+    if buffer.len() < 4 {
+        return Err(VanityError::InvalidFormat(
+            "Truncated public key blob".into(),
+        ));
+    }
+    let len = u32::from_be_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as usize;
+    if buffer.len() - 4 < len {
+        return Err(VanityError::InvalidFormat(
+            "Truncated public key blob".into(),
+        ));
+    }
+    let (field, rest) = buffer[4..].split_at(len);
+    *buffer = rest;
+    Ok(field)
+}
+
+/// Returns the public key as a single line terminated by exactly one newline,
+/// as expected by `authorized_keys` files.
+pub fn authorized_keys_line(ssh_key: &str) -> String {
+    format!("{}\n", ssh_key.trim_end_matches(['\r', '\n']))
+}
+
+/// Appends a public key to an `authorized_keys` file, creating it if needed.
+///
+/// If the file does not already end with a newline one is inserted first, so
+/// consecutive keys never end up concatenated on the same line. With
+/// `trailing_newline` set the appended key is itself newline-terminated.
+pub fn append_authorized_key(path: &Path, ssh_key: &str, trailing_newline: bool) -> Result<()> {
+    append_authorized_key_with_options(path, ssh_key, None, trailing_newline)
+}
+
+/// Like `append_authorized_key`, but puts `options` (checked with
+/// `validate_key_options`) in front of the key, e.g.
+/// `no-port-forwarding ssh-ed25519 AAAA...`.
+///
+/// Every call writes and flushes the whole line, so a streaming search
+/// leaves each key in the file as soon as it's found.
+pub fn append_authorized_key_with_options(
+    path: &Path,
+    ssh_key: &str,
+    options: Option<&str>,
+    trailing_newline: bool,
+) -> Result<()> {
+    if let Some(options) = options {
+        validate_key_options(options)?;
+    }
+
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(path)?;
+
+    // Check whether the existing content ends with a newline
+    let len = file.metadata()?.len();
+    let needs_separator = if len > 0 {
+        let mut last = [0u8; 1];
+        file.seek(SeekFrom::End(-1))?;
+        file.read_exact(&mut last)?;
+        last[0] != b'\n'
+    } else {
+        false
+    };
+
+    let mut line = String::new();
+    if needs_separator {
+        line.push('\n');
+    }
+    if let Some(options) = options {
+        line.push_str(options);
+        line.push(' ');
+    }
+    if trailing_newline {
+        line.push_str(&authorized_keys_line(ssh_key));
+    } else {
+        line.push_str(ssh_key.trim_end_matches(['\r', '\n']));
+    }
 
-    // Return just the base64 encoded part
-    Ok(parts[1].to_string())
+    file.write_all(line.as_bytes())?;
+    file.flush()?;
+
+    Ok(())
 }
 
 /// Helper function to write a length-prefixed string to a Vec<u8>
@@ -63,7 +339,7 @@ fn write_length_prefixed_string(buffer: &mut Vec<u8>, s: &str) -> Result<()> {
 fn write_length_prefixed_bytes(buffer: &mut Vec<u8>, bytes: &[u8]) -> Result<()> {
     // Write the 4-byte length prefix in big-endian format
     buffer
-        .write_u32::<BigEndian>(bytes.len() as u32)
+        .write_u32::<BigEndian>(length_prefix(bytes.len())?)
         .map_err(|e| VanityError::EncodingError(e.to_string()))?;
 
     // Write the actual bytes
@@ -0,0 +1,110 @@
+// src/ssh/randomart.rs
+
+use super::public_key::{ssh_public_key_blob, take_length_prefixed};
+use super::{KeyType, ED25519_KEY_TYPE, RSA_KEY_TYPE};
+use crate::error::{Result, VanityError};
+use sha2::{Digest, Sha256};
+
+/// Width and height of the randomart field, as in OpenSSH
+const FIELD_WIDTH: usize = 17;
+const FIELD_HEIGHT: usize = 9;
+
+/// Symbols for how often the bishop visited a cell, followed by the start
+/// and end markers
+const SYMBOLS: &[u8] = b" .o+=*BOX@%&#/^SE";
+const START: usize = SYMBOLS.len() - 2;
+const END: usize = SYMBOLS.len() - 1;
+
+/// Draws the "randomart" of a fingerprint digest with the drunken bishop
+/// algorithm, exactly like `ssh-keygen -lv`.
+///
+/// `title` is the key description shown in the top border, e.g.
+/// `ED25519 256`, and `hash_name` the digest named in the bottom border,
+/// e.g. `SHA256`. The result has no trailing newline.
+pub fn randomart(digest: &[u8], title: &str, hash_name: &str) -> String {
+    let mut field = [[0usize; FIELD_HEIGHT]; FIELD_WIDTH];
+    let (mut x, mut y) = (FIELD_WIDTH / 2, FIELD_HEIGHT / 2);
+
+    // Each byte moves the bishop four times, two bits per move, low bits first
+    for &byte in digest {
+        let mut input = byte;
+        for _ in 0..4 {
+            x = if input & 0x1 != 0 {
+                (x + 1).min(FIELD_WIDTH - 1)
+            } else {
+                x.saturating_sub(1)
+            };
+            y = if input & 0x2 != 0 {
+                (y + 1).min(FIELD_HEIGHT - 1)
+            } else {
+                y.saturating_sub(1)
+            };
+            if field[x][y] < START - 1 {
+                field[x][y] += 1;
+            }
+            input >>= 2;
+        }
+    }
+    field[FIELD_WIDTH / 2][FIELD_HEIGHT / 2] = START;
+    field[x][y] = END;
+
+    let mut art = border(&format!("[{}]", title));
+    art.push('\n');
+    for y in 0..FIELD_HEIGHT {
+        art.push('|');
+        for column in &field {
+            art.push(SYMBOLS[column[y]] as char);
+        }
+        art.push_str("|\n");
+    }
+    art.push_str(&border(&format!("[{}]", hash_name)));
+    art
+}
+
+/// Draws the randomart of an OpenSSH public key line's SHA256 fingerprint,
+/// titled with the key type and size like `ssh-keygen -lv` does
+pub fn ssh_key_randomart(ssh_key: &str) -> Result<String> {
+    let blob = ssh_public_key_blob(ssh_key)?;
+    let title = key_title(&blob)?;
+    Ok(randomart(&Sha256::digest(&blob), &title, "SHA256"))
+}
+
+/// Returns the `TYPE BITS` description of a public key blob
+fn key_title(blob: &[u8]) -> Result<String> {
+    let mut rest = blob;
+    let key_type = take_length_prefixed(&mut rest)?;
+    if key_type == ED25519_KEY_TYPE.as_bytes() {
+        Ok("ED25519 256".to_string())
+    } else if key_type == KeyType::SkEd25519.as_str().as_bytes() {
+        Ok("ED25519-SK 256".to_string())
+    } else if key_type == RSA_KEY_TYPE.as_bytes() {
+        let _exponent = take_length_prefixed(&mut rest)?;
+        let modulus = take_length_prefixed(&mut rest)?;
+        Ok(format!("RSA {}", significant_bits(modulus)))
+    } else {
+        Err(VanityError::InvalidFormat(format!(
+            "Unsupported key type {}",
+            String::from_utf8_lossy(key_type)
+        )))
+    }
+}
+
+/// Number of significant bits in a big-endian unsigned number
+fn significant_bits(number: &[u8]) -> usize {
+    match number.iter().position(|&byte| byte != 0) {
+        Some(first) => (number.len() - first) * 8 - number[first].leading_zeros() as usize,
+        None => 0,
+    }
+}
+
+/// A `+---[label]---+` border with the label centred
+fn border(label: &str) -> String {
+    let label = if label.len() > FIELD_WIDTH {
+        &label[..FIELD_WIDTH]
+    } else {
+        label
+    };
+    let left = (FIELD_WIDTH - label.len()) / 2;
+    let right = FIELD_WIDTH - label.len() - left;
+    format!("+{}{}{}+", "-".repeat(left), label, "-".repeat(right))
+}
@@ -0,0 +1,76 @@
+// src/ssh/rsa_key.rs
+
+use super::private_key::{
+    encode_openssh_private_key, write_length_prefixed_bytes, write_length_prefixed_string,
+};
+use super::{format_public_key_line, KeyType, OPENSSH_WRAP_WIDTH, RSA_KEY_TYPE};
+use crate::error::{Result, VanityError};
+use rsa::traits::{PrivateKeyParts, PublicKeyParts};
+use rsa::{BigUint, RsaPrivateKey};
+use zeroize::Zeroizing;
+
+/// Encodes an RSA public key in OpenSSH format.
+/// Returns a string in the format "ssh-rsa BASE64ENCODED_KEY [comment]"
+pub fn encode_ssh_rsa_public_key(
+    key: &impl PublicKeyParts,
+    comment: Option<&str>,
+) -> Result<String> {
+    format_public_key_line(KeyType::Rsa, &rsa_public_blob(key)?, comment)
+}
+
+/// Encodes an RSA key pair in OpenSSH private key format with a
+/// caller-chosen check integer, like `encode_ssh_private_key_with`.
+pub fn encode_ssh_rsa_private_key_with(
+    key: &RsaPrivateKey,
+    comment: Option<&str>,
+    check_int: u32,
+) -> Result<String> {
+    let primes = key.primes();
+    if primes.len() != 2 {
+        return Err(VanityError::EncodingError(
+            "OpenSSH only supports two-prime RSA keys".into(),
+        ));
+    }
+    let iqmp = key
+        .crt_coefficient()
+        .ok_or_else(|| VanityError::EncodingError("RSA primes are not coprime".into()))?;
+
+    // OpenSSH orders the private fields n, e, d, iqmp, p, q
+    let mut key_fields = Zeroizing::new(Vec::new());
+    write_length_prefixed_string(&mut key_fields, RSA_KEY_TYPE)?;
+    write_mpint(&mut key_fields, key.n())?;
+    write_mpint(&mut key_fields, key.e())?;
+    write_mpint(&mut key_fields, key.d())?;
+    write_mpint(&mut key_fields, &iqmp)?;
+    write_mpint(&mut key_fields, &primes[0])?;
+    write_mpint(&mut key_fields, &primes[1])?;
+
+    encode_openssh_private_key(
+        &rsa_public_blob(key)?,
+        &key_fields,
+        comment,
+        check_int,
+        OPENSSH_WRAP_WIDTH,
+    )
+}
+
+/// Builds the public key blob: the key type, then the exponent and modulus
+fn rsa_public_blob(key: &impl PublicKeyParts) -> Result<Vec<u8>> {
+    let mut blob = Vec::new();
+    write_length_prefixed_string(&mut blob, RSA_KEY_TYPE)?;
+    write_mpint(&mut blob, key.e())?;
+    write_mpint(&mut blob, key.n())?;
+    Ok(blob)
+}
+
+/// Writes an SSH mpint: a big-endian two's complement number without leading
+/// zero bytes, so positive numbers with the top bit set gain a zero byte
+fn write_mpint(buffer: &mut Vec<u8>, value: &BigUint) -> Result<()> {
+    let mut bytes = value.to_bytes_be();
+    if bytes == [0] {
+        bytes.clear();
+    } else if bytes[0] & 0x80 != 0 {
+        bytes.insert(0, 0);
+    }
+    write_length_prefixed_bytes(buffer, &bytes)
+}
@@ -0,0 +1,64 @@
+// src/state.rs
+use crate::error::{Result, VanityError};
+use crate::PerformanceMetrics;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
+
+/// Lifetime totals of a search that is stopped and restarted, kept in the
+/// `--state-file` as JSON
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct SearchState {
+    /// Keys generated by every run so far
+    pub total_attempts: u64,
+    /// Matches found by every run so far
+    pub matches_found: u64,
+    /// Wall-clock seconds spent searching by every run so far
+    pub wall_time: f64,
+}
+
+impl SearchState {
+    /// Reads the state left by earlier runs, or an empty state if `path`
+    /// doesn't exist yet
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(SearchState::default()),
+            Err(e) => return Err(e.into()),
+        };
+        serde_json::from_str(&contents).map_err(|e| {
+            VanityError::InvalidFormat(format!("invalid state file {}: {}", path.display(), e))
+        })
+    }
+
+    /// This state plus the current run's `metrics` after `wall_time` of
+    /// searching
+    pub fn with_run(&self, metrics: &PerformanceMetrics, wall_time: Duration) -> Self {
+        SearchState {
+            total_attempts: self.total_attempts + metrics.attempts,
+            matches_found: self.matches_found + metrics.matches_found,
+            wall_time: self.wall_time + wall_time.as_secs_f64(),
+        }
+    }
+
+    /// Writes the state to `path`, replacing the file atomically like
+    /// `prometheus::write_textfile` so a crash never leaves half of it
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let json =
+            serde_json::to_string(self).map_err(|e| VanityError::EncodingError(e.to_string()))?;
+
+        let mut temp = tempfile::Builder::new()
+            .prefix(".vanityssh-state-")
+            .tempfile_in(dir)?;
+        temp.write_all(json.as_bytes())?;
+        temp.write_all(b"\n")?;
+        temp.persist(path).map_err(|e| e.error)?;
+        Ok(())
+    }
+}
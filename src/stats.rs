@@ -0,0 +1,105 @@
+// src/stats.rs
+
+use crate::matcher::{self, MatchTarget};
+use crate::ssh::ED25519_BODY_PREFIX;
+use crate::thread_pool::{KeyMatch, ThreadPoolConfig};
+use regex::Regex;
+
+/// Computes a "vanity score" for a match: roughly log2 of the number of
+/// random keys you would expect to generate to hit this particular match.
+///
+/// The score is built from the characters the pattern actually matched in
+/// the base64 body of the public key:
+///
+/// * characters inside the fixed `AAAAC3NzaC1lZDI1NTE5AAAAI` header are free
+///   and score 0 bits
+/// * the first variable character can only take 16 values and scores 4 bits
+/// * every other character scores 6 bits (one of 64), or 5 bits for a letter
+///   when matching case-insensitively, since either case counts
+///
+/// Fingerprint matches have no fixed header: every character scores as a
+/// variable one, which makes them rarer than the same string in the body.
+/// Hex fingerprint matches score 4 bits (one of 16) per character.
+///
+/// A match further into the key is easier to come by than one at the start of
+/// the variable region, so `log2(1 + offset)` is subtracted, where `offset` is
+/// the distance from the first variable character. The score never drops
+/// below zero, and is zero when the pattern doesn't match the key at all.
+pub fn vanity_score(key_match: &KeyMatch, config: &ThreadPoolConfig) -> f64 {
+    let body = match matcher::match_text(&key_match.public_key, config.match_target) {
+        Ok(body) => body,
+        Err(_) => return 0.0,
+    };
+
+    // Score against whichever of several OR'd patterns actually matched
+    let pattern = if key_match.matched_pattern.is_empty() {
+        &config.pattern
+    } else {
+        &key_match.matched_pattern
+    };
+    let regex = match Regex::new(&matcher::effective_pattern(pattern, config.case_sensitive)) {
+        Ok(regex) => regex,
+        Err(_) => return 0.0,
+    };
+
+    let found = match regex.find(&body) {
+        Some(found) => found,
+        None => return 0.0,
+    };
+
+    let (fixed_len, first_variable_bits) = match config.match_target {
+        MatchTarget::Body => (ED25519_BODY_PREFIX.len(), 4.0),
+        MatchTarget::Fingerprint => (0, 6.0),
+        MatchTarget::FingerprintHex => (0, 4.0),
+    };
+    let bits: f64 = body[found.start()..found.end()]
+        .char_indices()
+        .map(|(i, c)| {
+            let position = found.start() + i;
+            if config.match_target == MatchTarget::FingerprintHex {
+                4.0
+            } else if position < fixed_len {
+                0.0
+            } else if position == fixed_len {
+                first_variable_bits
+            } else if !config.case_sensitive && c.is_ascii_alphabetic() {
+                5.0
+            } else {
+                6.0
+            }
+        })
+        .sum();
+
+    let offset = found.start().saturating_sub(fixed_len);
+    (bits - (1.0 + offset as f64).log2()).max(0.0)
+}
+
+/// Formats a large count compactly, e.g. `16.7M`
+pub fn format_count(count: f64) -> String {
+    const UNITS: [(f64, &str); 4] = [(1e12, "T"), (1e9, "B"), (1e6, "M"), (1e3, "K")];
+    UNITS
+        .iter()
+        .find(|(scale, _)| count >= *scale)
+        .map(|(scale, unit)| format!("{:.1}{}", count / scale, unit))
+        .unwrap_or_else(|| format!("{:.0}", count))
+}
+
+/// Formats a duration in seconds at a human scale, e.g. `3m` or `2.5 days`
+pub fn format_duration(seconds: f64) -> String {
+    const MINUTE: f64 = 60.0;
+    const HOUR: f64 = 60.0 * MINUTE;
+    const DAY: f64 = 24.0 * HOUR;
+    const YEAR: f64 = 365.0 * DAY;
+
+    if seconds < MINUTE {
+        format!("{:.0}s", seconds)
+    } else if seconds < HOUR {
+        format!("{:.0}m", seconds / MINUTE)
+    } else if seconds < DAY {
+        format!("{:.1}h", seconds / HOUR)
+    } else if seconds < YEAR {
+        format!("{:.1} days", seconds / DAY)
+    } else {
+        format!("{:.1} years", seconds / YEAR)
+    }
+}
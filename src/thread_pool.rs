@@ -1,128 +1,685 @@
 // src/thread_pool.rs
 // Updated: 2025-04-22 15:45:00 by kengggg
 
-use crate::error::Result;
-use crate::keygen;
-use crate::matcher;
-use crossbeam_channel::{bounded, Receiver};
+use crate::cores;
+use crate::error::{Result, VanityError};
+use crate::keygen::{self, KeyAlgorithm};
+use crate::matcher::{self, Encoding, MatchScope, MatchTarget};
+use crate::ssh::{public_key::extract_ssh_key_data, validate_comment};
+use crossbeam_channel::{bounded, Receiver, Sender, TrySendError};
+use rand::{CryptoRng, RngCore};
+use regex::{Regex, RegexSet};
 use std::sync::{
-    atomic::{AtomicBool, Ordering},
+    atomic::{AtomicBool, AtomicU64, Ordering},
     Arc,
 };
-use std::thread;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
 
 /// Represents a match found by a worker thread
 pub struct KeyMatch {
     pub public_key: String,
     pub private_key: String,
+    /// Keys generated by the finding thread, this one included
     pub attempts: u64,
+    /// Keys generated by all threads when the match was found
+    pub total_attempts: u64,
     pub thread_id: usize,
+    /// Named capture groups from the pattern, as `(name, value)` pairs
+    pub captures: Vec<(String, String)>,
+    /// The pattern that matched, which is `pattern` unless
+    /// `extra_patterns` were given
+    pub matched_pattern: String,
+    /// Whether the pattern also matches with case sensitivity, e.g. `cafe`
+    /// rather than `CaFe`. Always true for a case-sensitive search; a
+    /// case-insensitive one only checks with `prefer_exact` and is false
+    /// otherwise.
+    pub exact_case: bool,
+    /// The raw Ed25519 public key, or `None` for an RSA key
+    pub public_key_bytes: Option<[u8; 32]>,
+    /// The raw Ed25519 private key (its 32-byte seed), wiped when the match
+    /// is dropped, or `None` for an RSA key
+    pub private_key_bytes: Option<Zeroizing<[u8; 32]>>,
 }
 
 /// Represents a status update from worker threads
 pub struct StatusUpdate {
+    pub thread_id: usize,
     pub attempts: u64,
 }
 
 /// Configuration for the thread pool
+#[derive(Clone)]
 pub struct ThreadPoolConfig {
     pub pattern: String,
+    /// Further patterns, matched as a logical OR with `pattern`
+    pub extra_patterns: Vec<String>,
     pub thread_count: usize,
     pub case_sensitive: bool,
     pub streaming: bool,
     pub comment: Option<String>,
+    /// Delay between worker start times. Worker `n` sleeps `n * stagger`
+    /// before generating its first key, which avoids every thread hitting the
+    /// OS random number generator at the same instant on startup. Every
+    /// worker seeds its own generator from `OsRng`, so workers never share RNG
+    /// state regardless of this setting.
+    pub startup_stagger: Option<Duration>,
+    /// In streaming mode, drop a match instead of waiting when the match
+    /// channel is full, so a slow consumer never stalls the workers. A closed
+    /// channel still stops the worker.
+    pub drop_when_full: bool,
+    /// Seed for a deterministic ChaCha20 RNG instead of the OS RNG.
+    /// Insecure: anyone who knows the seed can regenerate the keys. Each
+    /// worker uses `keygen::thread_seed(seed, thread_id)`.
+    pub seed: Option<[u8; 32]>,
+    /// Only accept keys whose variable base64 region sorts within
+    /// `[low, high)`, so a search can be split across machines
+    pub range: Option<(String, String)>,
+    /// What the pattern is matched against
+    pub match_target: MatchTarget,
+    /// How the raw key is rendered for matching. Anything but `Base64`
+    /// ignores `match_target`.
+    pub encoding: Encoding,
+    /// How much of the public key line the pattern sees. `Full` adds the
+    /// key type in front of the body and only applies to the base64 body.
+    pub match_scope: MatchScope,
+    /// Only match the pattern against the first this many characters of the
+    /// body (or of its other encoding or fingerprint). The key type that
+    /// `MatchScope::Full` adds doesn't count towards the length.
+    pub match_length: Option<usize>,
+    /// Stop the search once this many keys have been generated in total.
+    /// Workers count in batches, so the total can overshoot by at most
+    /// one batch (`report_interval`) per thread.
+    pub max_attempts: Option<u64>,
+    /// Stop the search once this much wall-clock time has passed, keeping
+    /// whatever was found so far
+    pub timeout: Option<Duration>,
+    /// In streaming mode, stop the search once this many matches have been
+    /// received. Checked by the receiving side, so workers may have found a
+    /// few more that are discarded.
+    pub max_matches: Option<u64>,
+    /// Pin worker `i` to core `pin_cores[i % len]`
+    pub pin_cores: Option<Vec<usize>>,
+    /// Use this flag as the pool's terminate flag, so the search can be
+    /// stopped from outside (e.g. by a signal handler, or a GUI's "Stop"
+    /// button holding a clone). Setting it stops every worker, and the
+    /// search functions then return the metrics so far. The pool also sets
+    /// it when it shuts down, so use a fresh flag for each search.
+    pub terminate: Option<Arc<AtomicBool>>,
+    /// Reject matches whose key body contains a word from the built-in
+    /// blocklist
+    pub safe: bool,
+    /// Type of key to generate. RSA is thousands of times slower than the
+    /// default Ed25519.
+    pub algorithm: KeyAlgorithm,
+    /// How many keys each worker generates between status updates. A
+    /// smaller batch gives a more accurate progress display and stops closer
+    /// to `max_attempts`, which is only checked once per batch, at the cost of
+    /// more channel sends. Larger batches mean less channel traffic at high
+    /// key rates. Defaults to 50 for Ed25519 and 1 for RSA.
+    pub report_interval: Option<u64>,
+    /// How many matches can wait in the match channel. Once it's full, a
+    /// worker that finds another match waits for the receiver, or drops the
+    /// match with `drop_when_full`. Defaults to 32.
+    pub match_channel_capacity: usize,
+    /// How many status updates can wait in the status channel before a
+    /// worker has to wait for the receiver to catch up. A small channel keeps
+    /// the workers in step with a slow receiver, a large one lets them run
+    /// ahead. Defaults to 128.
+    pub status_channel_capacity: usize,
+    /// Cap the combined rate of all workers at this many keys per second.
+    /// Each worker gets an equal share and sleeps whenever it gets ahead of
+    /// it, e.g. to keep a laptop from overheating.
+    pub max_rate: Option<f64>,
+    /// Match case-insensitively, but re-test each match case-sensitively
+    /// and record the result in `KeyMatch::exact_case`
+    pub prefer_exact: bool,
+    /// A looser pattern, e.g. a shorter prefix of `pattern`, to count keys
+    /// that match it but not the search's own patterns. Matched like them,
+    /// against the same text and with the same case sensitivity.
+    pub near_pattern: Option<String>,
+}
+
+impl Default for ThreadPoolConfig {
+    fn default() -> Self {
+        ThreadPoolConfig {
+            pattern: String::new(),
+            extra_patterns: Vec::new(),
+            thread_count: num_cpus::get(),
+            case_sensitive: false,
+            streaming: false,
+            comment: None,
+            startup_stagger: None,
+            drop_when_full: false,
+            seed: None,
+            range: None,
+            match_target: MatchTarget::Body,
+            encoding: Encoding::Base64,
+            match_scope: MatchScope::Body,
+            match_length: None,
+            max_attempts: None,
+            timeout: None,
+            max_matches: None,
+            pin_cores: None,
+            terminate: None,
+            safe: false,
+            algorithm: KeyAlgorithm::Ed25519,
+            report_interval: None,
+            match_channel_capacity: 32,
+            status_channel_capacity: 128,
+            max_rate: None,
+            prefer_exact: false,
+            near_pattern: None,
+        }
+    }
+}
+
+/// Handle to a running thread pool that allows a clean shutdown
+pub struct ThreadPoolHandle {
+    pub match_receiver: Receiver<KeyMatch>,
+    pub status_receiver: Receiver<StatusUpdate>,
+    terminate: Arc<AtomicBool>,
+    attempts: Arc<AtomicU64>,
+    criteria: MatchCriteria,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ThreadPoolHandle {
+    /// Returns the shared flag used to tell the worker threads to stop
+    pub fn terminate_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.terminate)
+    }
+
+    /// Number of keys generated so far by all workers together. Workers add
+    /// to it in batches, so it lags by less than a batch per worker.
+    pub fn attempts(&self) -> u64 {
+        self.attempts.load(Ordering::Relaxed)
+    }
+
+    /// Number of keys so far that matched `ThreadPoolConfig::near_pattern`
+    /// but not the search's patterns; always 0 without a near pattern. Read
+    /// it just before `shutdown`, which consumes the handle.
+    pub fn near_misses(&self) -> u64 {
+        self.criteria.near_misses()
+    }
+
+    /// Signals all worker threads to stop and waits for them to exit.
+    /// Returns the exact number of keys they generated.
+    pub fn shutdown(self) -> u64 {
+        terminate_all(&self.terminate);
+
+        // Drop the receivers first so workers blocked on a full channel
+        // get a send error instead of waiting forever
+        drop(self.match_receiver);
+        drop(self.status_receiver);
+
+        for worker in self.workers {
+            let _ = worker.join();
+        }
+        self.attempts.load(Ordering::Relaxed)
+    }
+}
+
+/// How a key matched, as found by `MatchCriteria::check`
+pub(crate) struct Hit {
+    /// Index of the first matching pattern
+    index: usize,
+    captures: Vec<(String, String)>,
+    exact_case: bool,
+}
+
+/// The parts of a `ThreadPoolConfig` that decide whether a generated key is
+/// a match
+#[derive(Clone)]
+pub(crate) struct MatchCriteria {
+    /// `pattern` followed by `extra_patterns`
+    patterns: Arc<Vec<String>>,
+    case_sensitive: bool,
+    /// Each pattern compiled once and shared by every worker
+    regexes: Arc<Vec<Regex>>,
+    /// All the patterns in one set, when there is more than one
+    set: Option<Arc<RegexSet>>,
+    /// Every pattern taken apart, when they're all anchored literals that
+    /// plain comparisons can match without the regexes
+    literals: Option<Arc<Vec<matcher::AnchoredLiteral>>>,
+    /// Every literal has a prefix that's matched against the key body as
+    /// is, so Ed25519 candidates can be ruled out from their raw bytes with
+    /// `matcher::prefix_matches_raw` before encoding anything
+    raw_prefixes: bool,
+    /// Each pattern compiled case-sensitively, for re-testing the matches
+    /// of a case-insensitive `prefer_exact` search
+    exact_regexes: Option<Arc<Vec<Regex>>>,
+    /// The first pattern matches any text, e.g. `.*`, so every key passes
+    /// without rendering or matching anything
+    always_match: bool,
+    match_target: MatchTarget,
+    encoding: Encoding,
+    /// `"<key type> "`, prepended to the body for `MatchScope::Full`
+    scope_prefix: Option<String>,
+    match_length: Option<usize>,
+    range: Option<(String, String)>,
+    safe: bool,
+    near: Option<Arc<NearMiss>>,
 }
 
-/// Creates and manages a thread pool for generating and matching keys
-pub fn run_thread_pool(
-    config: ThreadPoolConfig,
-) -> Result<(Receiver<KeyMatch>, Receiver<StatusUpdate>)> {
+/// The `near_pattern` of a search and how many keys matched it but not the
+/// search's own patterns, shared by every worker
+struct NearMiss {
+    regex: Regex,
+    count: AtomicU64,
+}
+
+impl MatchCriteria {
+    /// Fails if the pattern isn't a valid regex
+    pub(crate) fn from_config(config: &ThreadPoolConfig) -> Result<Self> {
+        let patterns: Vec<String> = std::iter::once(&config.pattern)
+            .chain(&config.extra_patterns)
+            .cloned()
+            .collect();
+        let regexes = patterns
+            .iter()
+            .map(|pattern| matcher::compile_pattern(pattern, config.case_sensitive))
+            .collect::<Result<Vec<_>>>()?;
+        let set = if patterns.len() > 1 {
+            Some(Arc::new(matcher::compile_pattern_set(
+                &patterns,
+                config.case_sensitive,
+            )?))
+        } else {
+            None
+        };
+        let literals = patterns
+            .iter()
+            .map(|pattern| matcher::AnchoredLiteral::parse(pattern, config.case_sensitive))
+            .collect::<Option<Vec<_>>>()
+            .map(Arc::new);
+        // Counting near misses needs the body of every key
+        let raw_prefixes = config.encoding == Encoding::Base64
+            && config.match_target == MatchTarget::Body
+            && config.match_scope == MatchScope::Body
+            && config.near_pattern.is_none()
+            && literals
+                .as_ref()
+                .is_some_and(|literals| literals.iter().all(|literal| !literal.prefix.is_empty()));
+        let exact_regexes = if config.prefer_exact && !config.case_sensitive {
+            let exact = patterns
+                .iter()
+                .map(|pattern| matcher::compile_pattern(pattern, true))
+                .collect::<Result<Vec<_>>>()?;
+            Some(Arc::new(exact))
+        } else {
+            None
+        };
+
+        Ok(MatchCriteria {
+            patterns: Arc::new(patterns),
+            case_sensitive: config.case_sensitive,
+            regexes: Arc::new(regexes),
+            set,
+            literals,
+            raw_prefixes,
+            exact_regexes,
+            always_match: matcher::matches_everything(&config.pattern),
+            match_target: config.match_target,
+            encoding: config.encoding,
+            scope_prefix: match config.match_scope {
+                MatchScope::Body => None,
+                MatchScope::Full => Some(format!("{} ", config.algorithm.key_type())),
+            },
+            match_length: config.match_length,
+            range: config.range.clone(),
+            safe: config.safe,
+            near: match config.near_pattern {
+                Some(ref pattern) => Some(Arc::new(NearMiss {
+                    regex: matcher::compile_pattern(pattern, config.case_sensitive)?,
+                    count: AtomicU64::new(0),
+                })),
+                None => None,
+            },
+        })
+    }
+
+    /// Keys that matched the near pattern but not the search's patterns so
+    /// far, across every clone of these criteria
+    pub(crate) fn near_misses(&self) -> u64 {
+        self.near
+            .as_ref()
+            .map_or(0, |near| near.count.load(Ordering::Relaxed))
+    }
+
+    /// Whether an Ed25519 key with these raw bytes could match, judged
+    /// from the leading base64 characters alone when `raw_prefixes` allows.
+    /// A `true` still needs `check` on the full body.
+    fn may_match_raw(&self, public_key_bytes: &[u8; 32]) -> bool {
+        match self.literals {
+            Some(ref literals) if self.raw_prefixes => literals.iter().any(|literal| {
+                matcher::prefix_matches_raw(
+                    public_key_bytes,
+                    &literal.prefix,
+                    literal.case_sensitive,
+                )
+            }),
+            _ => true,
+        }
+    }
+
+    /// Returns how the key matched, if it is a match. `body` is the base64
+    /// body of the OpenSSH public key line and `public_key_bytes` the raw key.
+    pub(crate) fn check(&self, body: &str, public_key_bytes: &[u8]) -> Option<Hit> {
+        // Skip keys outside this machine's share of the search
+        if let Some((ref low, ref high)) = self.range {
+            if !matcher::in_lex_range(matcher::variable_region(body), low, high) {
+                return None;
+            }
+        }
+
+        // Nothing to render or match when the pattern accepts any text
+        if self.always_match {
+            if self.safe && matcher::contains_blocked_word(body) {
+                return None;
+            }
+            return Some(Hit {
+                index: 0,
+                captures: Vec::new(),
+                // Matches in any case, so exact whenever it's checked
+                exact_case: self.case_sensitive || self.exact_regexes.is_some(),
+            });
+        }
+
+        // The common case matches the body as is, without allocating
+        let rendered;
+        let text = match (self.encoding, self.match_target) {
+            (Encoding::Base64, MatchTarget::Body) => {
+                let body = matcher::truncate_text(body, self.match_length);
+                match self.scope_prefix {
+                    Some(ref prefix) => {
+                        rendered = format!("{}{}", prefix, body);
+                        rendered.as_str()
+                    }
+                    None => body,
+                }
+            }
+            (Encoding::Base64, MatchTarget::Fingerprint) => {
+                rendered = matcher::body_fingerprint(body).ok()?;
+                matcher::truncate_text(&rendered, self.match_length)
+            }
+            (Encoding::Base64, MatchTarget::FingerprintHex) => {
+                rendered = matcher::body_fingerprint_hex(body).ok()?;
+                matcher::truncate_text(&rendered, self.match_length)
+            }
+            (encoding, _) => {
+                rendered = matcher::encode_key(public_key_bytes, encoding).ok()?;
+                matcher::truncate_text(&rendered, self.match_length)
+            }
+        };
+        let index = match (&self.literals, &self.set) {
+            (Some(literals), _) => literals.iter().position(|literal| literal.matches(text)),
+            // Nearly every key misses, so only a hit pays for finding which
+            // pattern it was
+            (None, Some(set)) => {
+                if set.is_match(text) {
+                    set.matches(text).into_iter().next()
+                } else {
+                    None
+                }
+            }
+            (None, None) => matcher::matches_compiled(text, &self.regexes[0]).then_some(0),
+        };
+        let Some(index) = index else {
+            if let Some(ref near) = self.near {
+                if near.regex.is_match(text) {
+                    near.count.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            return None;
+        };
+
+        // Only matches need screening, so check them here
+        if self.safe && matcher::contains_blocked_word(body) {
+            return None;
+        }
+
+        // Only pay for capture extraction on an actual match
+        let captures = matcher::captures_compiled(text, &self.regexes[index]).unwrap_or_default();
+        let exact_case = match self.exact_regexes {
+            Some(ref exact) => exact[index].is_match(text),
+            None => self.case_sensitive,
+        };
+        Some(Hit {
+            index,
+            captures,
+            exact_case,
+        })
+    }
+
+    /// Generates one key and returns it if it matches.
+    ///
+    /// Ed25519 candidates that a literal prefix rules out from their raw
+    /// bytes are dropped first. The rest are checked against a body written
+    /// into `body`, a buffer reused across calls, and only encoded in full
+    /// on a match.
+    pub(crate) fn try_key<R: RngCore + CryptoRng>(
+        &self,
+        algorithm: KeyAlgorithm,
+        rng: &mut R,
+        comment: Option<&str>,
+        body: &mut String,
+    ) -> Result<Option<KeyMatch>> {
+        let found = match algorithm {
+            KeyAlgorithm::Ed25519 => {
+                let candidate = keygen::Ed25519Candidate::generate(rng);
+                if !self.may_match_raw(candidate.public_key_bytes()) {
+                    return Ok(None);
+                }
+                body.clear();
+                candidate.encode_body(body);
+                match self.check(body, candidate.public_key_bytes()) {
+                    Some(hit) => {
+                        let (public_key, private_key) = candidate.to_openssh(comment)?;
+                        let raw_keys =
+                            (*candidate.public_key_bytes(), candidate.private_key_bytes());
+                        Some((public_key, private_key, Some(raw_keys), hit))
+                    }
+                    None => None,
+                }
+            }
+            KeyAlgorithm::Rsa { .. } => {
+                // Prime generation dwarfs the encoding cost
+                let (public_key_bytes, public_key, private_key) =
+                    keygen::generate_openssh_key_pair_for(algorithm, rng, comment)?;
+                let rsa_body = extract_ssh_key_data(&public_key)?;
+                self.check(&rsa_body, &public_key_bytes)
+                    .map(|hit| (public_key, private_key, None, hit))
+            }
+        };
+
+        Ok(found.map(|(public_key, private_key, raw_keys, hit)| {
+            let (public_key_bytes, private_key_bytes) = raw_keys.unzip();
+            KeyMatch {
+                public_key,
+                private_key,
+                attempts: 0,
+                total_attempts: 0,
+                thread_id: 0,
+                captures: hit.captures,
+                matched_pattern: self.patterns[hit.index].clone(),
+                exact_case: hit.exact_case,
+                public_key_bytes,
+                private_key_bytes,
+            }
+        }))
+    }
+}
+
+/// Creates a thread pool for generating and matching keys and returns a
+/// handle that owns the worker threads. The pattern is compiled once here,
+/// so an invalid one is an error before any thread starts, as is a
+/// `thread_count` of 0.
+///
+/// Call `ThreadPoolHandle::shutdown` to stop the workers and wait for them;
+/// dropping the handle instead leaves them to exit on their own once they
+/// notice the closed channels.
+pub fn run_thread_pool(config: ThreadPoolConfig) -> Result<ThreadPoolHandle> {
     let thread_count = config.thread_count;
-    let pattern = config.pattern;
-    let case_sensitive = config.case_sensitive;
+    // Without workers nothing would ever be found
+    if thread_count == 0 {
+        return Err(VanityError::InvalidFormat(
+            "thread count must be >= 1".into(),
+        ));
+    }
+    let criteria = MatchCriteria::from_config(&config)?;
+    if let Some(ref comment) = config.comment {
+        validate_comment(comment)?;
+    }
     let streaming = config.streaming;
     let comment = config.comment;
+    let startup_stagger = config.startup_stagger;
+    let drop_when_full = streaming && config.drop_when_full;
+    let seed = config.seed;
+    let algorithm = config.algorithm;
+    // RSA keys are slow enough that every key is worth reporting
+    let batch_size = config
+        .report_interval
+        .filter(|&interval| interval > 0)
+        .unwrap_or(match algorithm {
+            KeyAlgorithm::Ed25519 => 50, // Report every 50 attempts
+            KeyAlgorithm::Rsa { .. } => 1,
+        });
+    let max_attempts = config.max_attempts;
+    // How long each worker should take per key to stay within max_rate
+    let key_interval = config
+        .max_rate
+        .filter(|&rate| rate > 0.0)
+        .and_then(|rate| Duration::try_from_secs_f64(thread_count as f64 / rate).ok());
+    let pin_cores = config.pin_cores.filter(|cores| !cores.is_empty());
 
     // Set up communication channels
-    let (match_sender, match_receiver) = bounded::<KeyMatch>(32);
-    let (status_sender, status_receiver) = bounded::<StatusUpdate>(128);
+    let (match_sender, match_receiver) = bounded::<KeyMatch>(config.match_channel_capacity);
+    let (status_sender, status_receiver) = bounded::<StatusUpdate>(config.status_channel_capacity);
 
     // Shared state
-    let terminate = Arc::new(AtomicBool::new(false));
+    let terminate = config
+        .terminate
+        .unwrap_or_else(|| Arc::new(AtomicBool::new(false)));
+    let attempt_counter = Arc::new(AtomicU64::new(0));
 
     // Spawn worker threads
+    let mut workers = Vec::with_capacity(thread_count);
     for thread_id in 0..thread_count {
         let thread_match_sender = match_sender.clone();
         let thread_status_sender = status_sender.clone();
-        let thread_pattern = pattern.clone();
+        let thread_criteria = criteria.clone();
         let thread_comment = comment.clone();
         let thread_terminate = Arc::clone(&terminate);
+        let thread_attempt_counter = Arc::clone(&attempt_counter);
+        let thread_core = pin_cores
+            .as_ref()
+            .map(|cores| cores[thread_id % cores.len()]);
+
+        let worker = thread::spawn(move || {
+            if let Some(core) = thread_core {
+                cores::pin_current_thread(core);
+            }
 
-        thread::spawn(move || {
             let mut local_attempts: u64 = 0;
             let mut last_reported = 0;
-            let batch_size = 50; // Report every 50 attempts
+            let mut rng = match seed {
+                Some(ref seed) => keygen::KeyRng::seeded(keygen::thread_seed(seed, thread_id)),
+                None => keygen::KeyRng::os_seeded(),
+            };
+
+            // Stagger startup so workers don't all seed at the same moment
+            if let Some(stagger) = startup_stagger {
+                thread::sleep(stagger * thread_id as u32);
+            }
+
+            let mut body = String::with_capacity(128);
+            let worker_start = Instant::now();
 
             // Worker thread loop
             while !thread_terminate.load(Ordering::Relaxed) {
-                // Generate a key pair
+                // Count the key about to be generated
                 local_attempts += 1;
 
+                // Generate a key and check it against the pattern
+                if let Ok(Some(key_match)) = thread_criteria.try_key(
+                    algorithm,
+                    &mut rng,
+                    thread_comment.as_deref(),
+                    &mut body,
+                ) {
+                    // Found a match!
+                    // Report the attempts since the last batch, this key
+                    // included
+                    let remaining = local_attempts - last_reported;
+                    let total =
+                        thread_attempt_counter.fetch_add(remaining, Ordering::Relaxed) + remaining;
+                    let _ = thread_status_sender.send(StatusUpdate {
+                        thread_id,
+                        attempts: remaining,
+                    });
+                    last_reported = local_attempts;
+                    log::debug!(
+                        "thread {}: match after {} local attempts ({} global) in {:.2?}",
+                        thread_id,
+                        local_attempts,
+                        total,
+                        worker_start.elapsed()
+                    );
+
+                    let key_match = KeyMatch {
+                        attempts: local_attempts,
+                        total_attempts: total,
+                        thread_id,
+                        ..key_match
+                    };
+
+                    // Send the match back to the main thread
+                    if !send_match(&thread_match_sender, key_match, drop_when_full) {
+                        // Channel closed, exit thread
+                        break;
+                    }
+
+                    // If not streaming, or the attempt budget is spent,
+                    // signal termination
+                    if !streaming || max_attempts.is_some_and(|max| total >= max) {
+                        thread_terminate.store(true, Ordering::Relaxed);
+                        break;
+                    }
+                }
+
                 // Report progress regularly
                 if local_attempts - last_reported >= batch_size {
                     // Send status update to main thread
                     let _ = thread_status_sender.send(StatusUpdate {
+                        thread_id,
                         attempts: batch_size,
                     });
                     last_reported = local_attempts;
+
+                    // Stop everyone once the global attempt budget is spent
+                    let total = thread_attempt_counter.fetch_add(batch_size, Ordering::Relaxed)
+                        + batch_size;
+                    log::trace!(
+                        "thread {}: {} local attempts ({} global) in {:.2?}",
+                        thread_id,
+                        local_attempts,
+                        total,
+                        worker_start.elapsed()
+                    );
+                    if max_attempts.is_some_and(|max| total >= max) {
+                        thread_terminate.store(true, Ordering::Relaxed);
+                        break;
+                    }
                 }
 
-                // Generate key
-                if let Ok((public_key, private_key)) = match thread_comment {
-                    Some(ref c) => keygen::generate_openssh_key_pair(Some(c)),
-                    None => keygen::generate_openssh_key_pair(None),
-                } {
-                    // Check if it matches the pattern
-                    match matcher::ssh_key_matches_pattern(
-                        &public_key,
-                        &thread_pattern,
-                        case_sensitive,
-                    ) {
-                        Ok(true) => {
-                            // Found a match!
-                            // Report any remaining attempts
-                            let remaining = local_attempts - last_reported;
-                            if remaining > 0 {
-                                let _ = thread_status_sender.send(StatusUpdate {
-                                    attempts: remaining,
-                                });
-                            }
-
-                            let key_match = KeyMatch {
-                                public_key,
-                                private_key,
-                                attempts: local_attempts,
-                                thread_id,
-                            };
-
-                            // Send the match back to the main thread
-                            if thread_match_sender.send(key_match).is_err() {
-                                // Channel closed, exit thread
-                                break;
-                            }
-
-                            // If not streaming, signal termination
-                            if !streaming {
-                                thread_terminate.store(true, Ordering::Relaxed);
-                                break;
-                            }
-                        }
-                        Ok(false) => {
-                            // No match, continue
-                        }
-                        Err(_) => {
-                            // Error matching, just continue
-                        }
+                // Sleep off any lead over this worker's share of max_rate
+                if let Some(interval) = key_interval {
+                    let due = interval.mul_f64(local_attempts as f64);
+                    let elapsed = worker_start.elapsed();
+                    if due > elapsed {
+                        thread::sleep(due - elapsed);
                     }
                 }
             }
@@ -130,15 +687,42 @@ pub fn run_thread_pool(
             // Final update for any remaining attempts
             let remaining = local_attempts - last_reported;
             if remaining > 0 {
+                thread_attempt_counter.fetch_add(remaining, Ordering::Relaxed);
                 let _ = thread_status_sender.send(StatusUpdate {
+                    thread_id,
                     attempts: remaining,
                 });
             }
         });
+        workers.push(worker);
     }
 
     // Return the receiver channels for the main thread to listen on
-    Ok((match_receiver, status_receiver))
+    Ok(ThreadPoolHandle {
+        match_receiver,
+        status_receiver,
+        terminate,
+        attempts: attempt_counter,
+        criteria,
+        workers,
+    })
+}
+
+/// Sends a match to the main thread.
+///
+/// Returns `false` only when the channel has been closed and the worker
+/// should exit. A full channel either blocks until there is room or, with
+/// `drop_when_full`, discards the match so the worker can keep going.
+fn send_match(sender: &Sender<KeyMatch>, key_match: KeyMatch, drop_when_full: bool) -> bool {
+    if !drop_when_full {
+        return sender.send(key_match).is_ok();
+    }
+
+    match sender.try_send(key_match) {
+        Ok(()) => true,
+        Err(TrySendError::Full(_)) => true,
+        Err(TrySendError::Disconnected(_)) => false,
+    }
 }
 
 /// Signal all threads to terminate
@@ -1,6 +1,8 @@
 // src/validation.rs
 use regex::Regex;
 use std::process;
+use std::time::Duration;
+use vanityssh_rust::{cores, matcher, stats, PerformanceMetrics};
 
 /// Validation utilities for command-line arguments and application inputs
 ///
@@ -26,8 +28,9 @@ pub fn validate_pattern(pattern: &str) {
 
 /// Validate the thread count or use the default CPU count
 ///
-/// Returns the user-specified thread count if provided and valid,
-/// otherwise returns the default value.
+/// Returns the user-specified thread count if provided, otherwise returns
+/// the default value. A count far above the CPU count gets a warning, and
+/// one above `cores::MAX_THREADS` is clamped to it.
 ///
 /// # Arguments
 ///
@@ -38,7 +41,25 @@ pub fn validate_pattern(pattern: &str) {
 ///
 /// The number of threads to use
 pub fn validate_threads(threads: Option<usize>, default: usize) -> usize {
-    threads.unwrap_or(default)
+    let Some(requested) = threads else {
+        return default;
+    };
+
+    let (thread_count, warning) = cores::effective_thread_count(requested, default);
+    match warning {
+        Some(cores::ThreadWarning::Oversubscribed) => eprintln!(
+            "Warning: {} threads is more than {}x the {} available CPUs and will mostly add overhead",
+            requested,
+            cores::OVERSUBSCRIBE_FACTOR,
+            default
+        ),
+        Some(cores::ThreadWarning::Clamped) => eprintln!(
+            "Warning: {} threads is too many, using {} instead",
+            requested, thread_count
+        ),
+        None => {}
+    }
+    thread_count
 }
 
 /// Display thread information to the user
@@ -59,3 +80,86 @@ pub fn display_thread_info(thread_count: usize, cpu_count: usize) {
         if cpu_count == 1 { "" } else { "s" }
     );
 }
+
+/// Print how many attempts the pattern is expected to take, and roughly how
+/// long that is at this machine's speed
+///
+/// Nothing is printed for patterns `matcher::estimate_difficulty` can't
+/// estimate. The speed comes from a short benchmark of the fastest
+/// `thread_count` cores, and is left out where cores can't be benchmarked.
+/// With `count` above one, the time for collecting all of them is added.
+pub fn display_estimate(pattern: &str, case_sensitive: bool, thread_count: usize, count: u64) {
+    let difficulty = match matcher::estimate_difficulty(pattern, case_sensitive) {
+        Some(difficulty) => difficulty,
+        None => return,
+    };
+    // validate_satisfiable has already warned about it
+    if !difficulty.is_possible() {
+        return;
+    }
+    let expected = difficulty.expected_attempts;
+    println!("Difficulty: {} per key", difficulty.odds());
+
+    let rate: f64 = cores::rank_cores(cores::benchmark_cores(Duration::from_millis(200)))
+        .iter()
+        .take(thread_count)
+        .map(|core| core.keys_per_second)
+        .sum();
+    if rate > 0.0 {
+        println!(
+            "Expected ~{} (~{} at {} keys/sec)",
+            difficulty,
+            stats::format_duration(expected / rate),
+            stats::format_count(rate)
+        );
+        if count > 1 {
+            let eta =
+                PerformanceMetrics::eta_for_matches(count, difficulty.per_key_probability, rate);
+            println!(
+                "Expected ~{} for all {} matches",
+                stats::format_duration(eta.as_secs_f64()),
+                count
+            );
+        }
+    } else {
+        println!("Expected ~{}", difficulty);
+        if count > 1 {
+            println!(
+                "Expected ~{} attempts for all {} matches",
+                stats::format_count(expected * count as f64),
+                count
+            );
+        }
+    }
+}
+
+/// Warn about patterns no key body can match, or with `strict` exit
+///
+/// Uses `matcher::unsatisfiable_reason`, so only anchored literals are
+/// checked; a search for one of these would otherwise run forever.
+///
+/// # Arguments
+///
+/// * `patterns` - Every pattern the search will try
+/// * `case_sensitive` - Whether the patterns are matched case-sensitively
+/// * `strict` - Treat an impossible pattern as an error
+pub fn validate_satisfiable<'a>(
+    patterns: impl IntoIterator<Item = &'a str>,
+    case_sensitive: bool,
+    strict: bool,
+) {
+    let mut impossible = false;
+    for pattern in patterns {
+        if let Some(reason) = matcher::unsatisfiable_reason(pattern, case_sensitive) {
+            let level = if strict { "Error" } else { "Warning" };
+            eprintln!(
+                "{}: no key can match the pattern '{}': {}",
+                level, pattern, reason
+            );
+            impossible = true;
+        }
+    }
+    if impossible && strict {
+        process::exit(1);
+    }
+}
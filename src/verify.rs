@@ -0,0 +1,93 @@
+// src/verify.rs
+
+use crate::error::{Result, VanityError};
+use crate::ssh::{private_key, public_key};
+use ed25519_dalek::{Signer, SigningKey, VerifyingKey};
+use std::fs;
+use std::path::Path;
+
+/// Checks that an OpenSSH private key and public key belong together.
+///
+/// The public key is derived from the private key's seed and compared with
+/// both the public key embedded in the private key and the provided public key.
+pub fn verify_key_pair(private_pem: &str, public_line: &str) -> Result<()> {
+    let (embedded_public, private_bytes, _comment) =
+        private_key::decode_ssh_private_key(private_pem)?;
+    let provided_public = public_key::decode_ssh_public_key(public_line)?;
+
+    let signing_key = signing_key_from(&private_bytes)?;
+    let derived_public = VerifyingKey::from(&signing_key).to_bytes();
+
+    if derived_public[..] != embedded_public[..] {
+        return Err(VanityError::KeyMismatch(
+            "private key does not match its own embedded public key".into(),
+        ));
+    }
+
+    if derived_public[..] != provided_public[..] {
+        return Err(VanityError::KeyMismatch(
+            "private key does not correspond to the provided public key".into(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Message signed by `verify_signature`
+const TEST_MESSAGE: &[u8] = b"vanityssh-rust key self-test";
+
+/// Signs a test message with an encoded OpenSSH private key and verifies the
+/// signature against the public key line, proving that the pair works as
+/// encoded rather than just as generated.
+///
+/// Any failure, including a key that doesn't decode, is reported as a
+/// `VanityError::KeyGenerationError`, since it means the generator produced
+/// a broken key.
+pub fn verify_signature(private_pem: &str, public_line: &str) -> Result<()> {
+    let broken = |e: VanityError| VanityError::KeyGenerationError(format!("broken key: {}", e));
+
+    let (_embedded_public, private_bytes, _comment) =
+        private_key::decode_ssh_private_key(private_pem).map_err(broken)?;
+    let signing_key = signing_key_from(&private_bytes).map_err(broken)?;
+
+    let public: [u8; 32] = public_key::decode_ssh_public_key(public_line)
+        .map_err(broken)?
+        .try_into()
+        .map_err(|_| {
+            broken(VanityError::InvalidFormat(
+                "public key is not 32 bytes".into(),
+            ))
+        })?;
+    let verifying_key = VerifyingKey::from_bytes(&public)
+        .map_err(|e| VanityError::KeyGenerationError(format!("invalid public key: {}", e)))?;
+
+    let signature = signing_key.sign(TEST_MESSAGE);
+    verifying_key
+        .verify_strict(TEST_MESSAGE, &signature)
+        .map_err(|e| {
+            VanityError::KeyGenerationError(format!("test signature does not verify: {}", e))
+        })
+}
+
+/// Builds the signing key from decoded OpenSSH private key data, which is
+/// the 32-byte seed followed by the public key
+fn signing_key_from(private_bytes: &[u8]) -> Result<SigningKey> {
+    let seed: [u8; 32] = private_bytes
+        .get(..32)
+        .and_then(|s| s.try_into().ok())
+        .ok_or_else(|| VanityError::InvalidFormat("Ed25519 private key is too short".into()))?;
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+/// Loads a private key file and a public key file and checks that they belong together.
+pub fn verify_key_pair_files(private_path: &Path, public_path: &Path) -> Result<()> {
+    let private_pem = read_key_file(private_path)?;
+    let public_line = read_key_file(public_path)?;
+    verify_key_pair(&private_pem, &public_line)
+}
+
+/// Helper function to read a key file with an error naming the path
+fn read_key_file(path: &Path) -> Result<String> {
+    fs::read_to_string(path)
+        .map_err(|e| VanityError::KeyFileError(format!("{}: {}", path.display(), e)))
+}
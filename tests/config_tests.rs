@@ -0,0 +1,199 @@
+// tests/config_tests.rs
+
+use vanityssh_rust::config::{Cli, Config, OwnedConfig};
+use vanityssh_rust::error::ConfigError;
+use vanityssh_rust::keygen::KeyAlgorithm;
+
+/// Builds an argument vector with the program name in front
+fn args(list: &[&str]) -> Vec<String> {
+    std::iter::once("vanityssh-rust")
+        .chain(list.iter().copied())
+        .map(String::from)
+        .collect()
+}
+
+#[test]
+fn test_try_parse_args_reads_options() {
+    let args = args(&[
+        "cafe",
+        "--threads",
+        "4",
+        "--streaming",
+        "--algorithm",
+        "rsa",
+    ]);
+    let cli = Cli::try_parse_args(&args).unwrap();
+    let file = OwnedConfig::default();
+    let config = Config::from_cli(&cli, &file).unwrap();
+
+    assert_eq!(config.pattern, "cafe");
+    assert_eq!(config.threads, Some(4));
+    assert!(config.streaming);
+    assert!(!config.case_sensitive);
+    assert!(matches!(config.algorithm, KeyAlgorithm::Rsa { .. }));
+}
+
+#[test]
+fn test_config_try_parse_args_wrapper() {
+    let file = OwnedConfig {
+        comment: Some("file@example".into()),
+        ..Default::default()
+    };
+    let mut cli = Cli::default();
+    let config = Config::try_parse_args(&args(&["cafe", "-t", "2"]), &mut cli, &file).unwrap();
+    assert_eq!(config.pattern, "cafe");
+    assert_eq!(config.threads, Some(2));
+    assert_eq!(config.comment, Some("file@example"));
+
+    assert!(matches!(
+        Config::try_parse_args(&args(&["--help"]), &mut Cli::default(), &file),
+        Err(ConfigError::HelpRequested)
+    ));
+}
+
+#[test]
+fn test_try_parse_args_extra_patterns() {
+    let args = args(&["cafe", "beef", "--pattern", "f00d"]);
+    let cli = Cli::try_parse_args(&args).unwrap();
+    let file = OwnedConfig::default();
+    let config = Config::from_cli(&cli, &file).unwrap();
+
+    assert_eq!(config.pattern, "cafe");
+    assert_eq!(config.extra_patterns, vec!["beef", "f00d"]);
+}
+
+#[test]
+fn test_try_parse_args_command_line_overrides_file() {
+    let file = OwnedConfig {
+        pattern: Some("from-file".into()),
+        threads: Some(2),
+        comment: Some("file@example".into()),
+        ..Default::default()
+    };
+
+    let cli = Cli::try_parse_args(&args(&["cli", "--threads", "8"])).unwrap();
+    let config = Config::from_cli(&cli, &file).unwrap();
+    assert_eq!(config.pattern, "cli");
+    assert_eq!(config.threads, Some(8));
+    assert_eq!(config.comment, Some("file@example"));
+}
+
+#[test]
+fn test_try_parse_args_errors() {
+    let file = OwnedConfig::default();
+    let parse = |list: &[&str]| {
+        Cli::try_parse_args(&args(list))
+            .and_then(|cli| Config::from_cli(&cli, &file).map(|_| ()))
+            .err()
+    };
+
+    assert_eq!(parse(&[]), Some(ConfigError::NoArguments));
+    assert_eq!(parse(&["--help"]), Some(ConfigError::HelpRequested));
+    assert_eq!(
+        parse(&["cafe", "--bogus"]),
+        Some(ConfigError::UnknownOption("--bogus".into()))
+    );
+    assert_eq!(
+        parse(&["cafe", "--threads"]),
+        Some(ConfigError::MissingValue {
+            option: "--threads".into(),
+            expected: "a value",
+        })
+    );
+    assert_eq!(
+        parse(&["cafe", "--threads", "0"]),
+        Some(ConfigError::InvalidValue {
+            option: "--threads".into(),
+            expected: "a positive integer",
+        })
+    );
+    assert_eq!(
+        parse(&["cafe", "--max-rate", "-5"]),
+        Some(ConfigError::InvalidValue {
+            option: "--max-rate".into(),
+            expected: "a positive number",
+        })
+    );
+    assert!(matches!(
+        parse(&["cafe", "--encoding", "rot13"]),
+        Some(ConfigError::ParseError { ref option, .. }) if option == "--encoding"
+    ));
+    assert!(matches!(
+        parse(&["cafe", "--prefix", "ab"]),
+        Some(ConfigError::ConflictingOptions(_))
+    ));
+    assert!(matches!(
+        parse(&["cafe", "--match-fingerprint", "--match", "fingerprint-hex"]),
+        Some(ConfigError::ConflictingOptions(_))
+    ));
+    assert!(matches!(
+        parse(&["cafe", "--json", "--output-format", "csv"]),
+        Some(ConfigError::ConflictingOptions(_))
+    ));
+    assert!(matches!(
+        parse(&["cafe", "--output-format", "xml"]),
+        Some(ConfigError::ParseError { ref option, .. }) if option == "--output-format"
+    ));
+    assert!(matches!(
+        parse(&["cafe", "--interactive", "--daemon"]),
+        Some(ConfigError::ConflictingOptions(_))
+    ));
+    assert_eq!(parse(&["--streaming"]), Some(ConfigError::NoPattern));
+}
+
+#[test]
+fn test_config_error_messages_match_the_cli() {
+    let error = ConfigError::InvalidValue {
+        option: "--count".into(),
+        expected: "a positive integer",
+    };
+    assert_eq!(error.to_string(), "--count requires a positive integer");
+    assert_eq!(
+        ConfigError::UnknownOption("--bogus".into()).to_string(),
+        "Unknown option: --bogus"
+    );
+}
+
+#[test]
+fn test_try_parse_args_short_and_equals_forms() {
+    let file = OwnedConfig::default();
+    for list in [
+        &["cafe", "--threads=4", "--count=2"][..],
+        &["cafe", "-t", "4", "-n", "2"][..],
+        &["-t4", "cafe", "--threads", "1", "-n", "2", "--threads", "4"][..],
+    ] {
+        let cli = Cli::try_parse_args(&args(list)).unwrap();
+        let config = Config::from_cli(&cli, &file).unwrap();
+        assert_eq!(config.pattern, "cafe");
+        assert_eq!(config.threads, Some(4), "{:?}", list);
+        assert_eq!(config.count, Some(2), "{:?}", list);
+    }
+}
+
+#[test]
+fn test_try_parse_args_keeps_pattern_order() {
+    let cli = Cli::try_parse_args(&args(&["--pattern", "f00d", "cafe", "-q", "beef"])).unwrap();
+    assert_eq!(cli.patterns, vec!["f00d", "cafe", "beef"]);
+
+    let file = OwnedConfig::default();
+    let config = Config::from_cli(&cli, &file).unwrap();
+    assert_eq!(config.pattern, "f00d");
+    assert_eq!(config.extra_patterns, vec!["cafe", "beef"]);
+    assert!(config.quiet);
+}
+
+#[test]
+fn test_try_parse_args_help_and_version() {
+    assert_eq!(
+        Cli::try_parse_args(&args(&["-h"])).err(),
+        Some(ConfigError::HelpRequested)
+    );
+    assert_eq!(
+        Cli::try_parse_args(&args(&["--version"])).err(),
+        Some(ConfigError::VersionRequested)
+    );
+    assert!(matches!(
+        Cli::try_parse_args(&args(&["cafe", "--range", "a"])),
+        Err(ConfigError::Usage(_))
+    ));
+}
@@ -0,0 +1,35 @@
+// tests/keygen_tests.rs
+//
+// Kept apart from tests.rs, and to a single test, so that counting this
+// process's threads isn't thrown off by other searches running at the same
+// time.
+
+use vanityssh_rust::keygen::find_matching_key;
+use vanityssh_rust::verify;
+
+/// Threads currently running in this process
+#[cfg(target_os = "linux")]
+fn thread_count() -> usize {
+    std::fs::read_dir("/proc/self/task").unwrap().count()
+}
+
+#[test]
+fn test_find_matching_key() {
+    #[cfg(target_os = "linux")]
+    let before = thread_count();
+
+    let key_match = find_matching_key(".*", false, Some("me@example.com"), 4).unwrap();
+    assert!(key_match.public_key.starts_with("ssh-ed25519 "));
+    assert!(key_match.public_key.ends_with(" me@example.com"));
+    assert!(key_match.attempts >= 1);
+    verify::verify_signature(&key_match.private_key, &key_match.public_key).unwrap();
+
+    for _ in 0..5 {
+        find_matching_key("^a", false, None, 4).unwrap();
+    }
+    assert!(find_matching_key("[", false, None, 4).is_err());
+
+    // Every worker has been joined
+    #[cfg(target_os = "linux")]
+    assert_eq!(thread_count(), before);
+}
@@ -2,7 +2,14 @@
 // Updated: 2025-04-22 14:12:15 by kengggg
 
 use std::time::Duration;
-use vanityssh_rust::{keygen, matcher, stream_keys_and_match, PerformanceMetrics};
+use vanityssh_rust::thread_pool::{KeyMatch, ThreadPoolConfig};
+use vanityssh_rust::{
+    collect_vanity_keys, estimated_attempts, find_key_inline, find_key_inline_with, keygen,
+    matcher, output, quality, stats, stream_with_handlers, ActiveClock, PerformanceMetrics,
+    VanitySearch,
+};
+#[cfg(feature = "cli")]
+use vanityssh_rust::{run_search, stream_keys_and_match, OutputOptions};
 
 #[test]
 fn test_generate_key_pair() {
@@ -94,6 +101,58 @@ fn test_performance_metrics_calculation() {
 }
 
 #[test]
+fn test_prometheus_render() {
+    let mut metrics = PerformanceMetrics::new();
+    metrics.update(1000, 2, Duration::from_secs(4));
+
+    let text = vanityssh_rust::prometheus::render(&metrics);
+    assert!(
+        text.contains("# TYPE vanityssh_attempts_total counter\nvanityssh_attempts_total 1000\n")
+    );
+    assert!(text.contains("vanityssh_matches_total 2\n"));
+    assert!(
+        text.contains("# TYPE vanityssh_keys_per_second gauge\nvanityssh_keys_per_second 250\n")
+    );
+}
+
+#[test]
+fn test_eta_for_matches() {
+    // One in 1024 keys matches, at 512 keys/sec: 2s per match on average
+    let p = 1.0 / 1024.0;
+    assert_eq!(
+        PerformanceMetrics::eta_for_matches(1, p, 512.0),
+        Duration::from_secs(2)
+    );
+    assert_eq!(
+        PerformanceMetrics::eta_for_matches(5, p, 512.0),
+        Duration::from_secs(10)
+    );
+    assert_eq!(
+        PerformanceMetrics::eta_for_matches(0, p, 512.0),
+        Duration::ZERO
+    );
+
+    // Never happens
+    assert_eq!(
+        PerformanceMetrics::eta_for_matches(3, 0.0, 512.0),
+        Duration::MAX
+    );
+    assert_eq!(
+        PerformanceMetrics::eta_for_matches(3, p, 0.0),
+        Duration::MAX
+    );
+    assert_eq!(
+        PerformanceMetrics::eta_for_matches(3, f64::NAN, 512.0),
+        Duration::MAX
+    );
+    assert_eq!(
+        PerformanceMetrics::eta_for_matches(u64::MAX, 1e-300, 1.0),
+        Duration::MAX
+    );
+}
+
+#[test]
+#[cfg(feature = "cli")]
 fn test_stream_keys_with_simple_pattern() {
     // Use a pattern that should match any key (will match very quickly)
     let result = stream_keys_and_match(".", false, false);
@@ -105,3 +164,1184 @@ fn test_stream_keys_with_simple_pattern() {
     assert!(metrics.attempts > 0);
     assert!(metrics.keys_per_second > 0.0);
 }
+
+#[test]
+fn test_collect_vanity_keys() {
+    let config = ThreadPoolConfig {
+        pattern: ".*".to_string(),
+        thread_count: 2,
+        case_sensitive: false,
+        streaming: false,
+        comment: None,
+        ..Default::default()
+    };
+
+    let keys = collect_vanity_keys(config, 2).unwrap();
+
+    assert_eq!(keys.len(), 2);
+    assert_ne!(keys[0].public_key, keys[1].public_key);
+    assert_ne!(keys[0].private_key, keys[1].private_key);
+}
+
+#[test]
+fn test_key_match_raw_bytes_reconstruct_the_keys() {
+    use vanityssh_rust::ssh;
+
+    let config = ThreadPoolConfig {
+        pattern: ".*".to_string(),
+        thread_count: 1,
+        comment: Some("raw@example".to_string()),
+        ..Default::default()
+    };
+    let pooled = collect_vanity_keys(config.clone(), 1).unwrap().remove(0);
+    let inline = find_key_inline(&config).unwrap().unwrap();
+    let iterated = keygen::matching_keys("a", false, Some("raw@example"))
+        .next()
+        .unwrap()
+        .unwrap();
+
+    for key_match in [pooled, inline, iterated] {
+        let public = key_match.public_key_bytes.unwrap();
+        let private = key_match.private_key_bytes.as_deref().copied().unwrap();
+        assert_eq!(
+            ssh::encode_ssh_public_key(&public, Some("raw@example")).unwrap(),
+            key_match.public_key
+        );
+
+        // The private key file holds the seed followed by the public key
+        let (decoded_public, decoded_private, comment) =
+            ssh::private_key::decode_ssh_private_key(&key_match.private_key).unwrap();
+        assert_eq!(decoded_public, public);
+        assert_eq!(decoded_private, [private, public].concat());
+        assert_eq!(comment, "raw@example");
+    }
+}
+
+#[test]
+fn test_vanity_score_longer_prefix_scores_higher() {
+    let (public_key, private_key) = keygen::generate_openssh_key_pair(None).unwrap();
+    let body = public_key.split_whitespace().nth(1).unwrap().to_string();
+    let key_match = KeyMatch {
+        public_key,
+        private_key,
+        attempts: 1,
+        total_attempts: 1,
+        thread_id: 0,
+        captures: Vec::new(),
+        matched_pattern: String::new(),
+        exact_case: false,
+        public_key_bytes: None,
+        private_key_bytes: None,
+    };
+
+    let score_for = |pattern: String| {
+        let config = ThreadPoolConfig {
+            pattern,
+            case_sensitive: true,
+            ..Default::default()
+        };
+        stats::vanity_score(&key_match, &config)
+    };
+
+    // Literal runs taken from the variable part of the key body
+    let short = score_for(regex::escape(&body[26..28]));
+    let long = score_for(regex::escape(&body[26..32]));
+    let header = score_for(regex::escape(&body[..20]));
+
+    assert!(long > short, "{} should exceed {}", long, short);
+    assert_eq!(header, 0.0, "The fixed header is not impressive");
+}
+
+#[test]
+fn test_pattern_captures_named_groups() {
+    let key = "AAAAC3NzaC1lZDI1NTE5AAAAIcafe42xyz";
+
+    let captures = matcher::pattern_captures(key, "(?P<word>[a-z]{4})(?P<num>[0-9]+)", true)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        captures,
+        vec![
+            ("word".to_string(), "cafe".to_string()),
+            ("num".to_string(), "42".to_string()),
+        ]
+    );
+
+    // No match yields None, a match without named groups yields an empty list
+    assert!(matcher::pattern_captures(key, "(?P<x>qqq)", true)
+        .unwrap()
+        .is_none());
+    assert!(matcher::pattern_captures(key, "cafe", true)
+        .unwrap()
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_thread_pool_reports_captures() {
+    let config = ThreadPoolConfig {
+        // The body always begins with this fixed header
+        pattern: "^(?P<header>AAAAC3)".to_string(),
+        thread_count: 1,
+        case_sensitive: true,
+        ..Default::default()
+    };
+
+    let keys = collect_vanity_keys(config, 1).unwrap();
+    assert_eq!(
+        keys[0].captures,
+        vec![("header".to_string(), "AAAAC3".to_string())]
+    );
+}
+
+#[test]
+fn test_in_lex_range() {
+    let region = "Mxyz";
+
+    assert!(matcher::in_lex_range(region, "A", "a"), "in range");
+    assert!(!matcher::in_lex_range(region, "N", "a"), "below range");
+    assert!(!matcher::in_lex_range(region, "A", "M"), "above range");
+
+    // The low bound is inclusive and the high bound exclusive
+    assert!(matcher::in_lex_range("M", "M", "N"));
+    assert!(!matcher::in_lex_range("N", "M", "N"));
+}
+
+#[test]
+fn test_thread_pool_respects_range() {
+    let config = ThreadPoolConfig {
+        pattern: ".*".to_string(),
+        thread_count: 2,
+        // The first variable character is always one of A-P
+        range: Some(("C".to_string(), "E".to_string())),
+        ..Default::default()
+    };
+
+    for key in collect_vanity_keys(config, 3).unwrap() {
+        let body = key.public_key.split_whitespace().nth(1).unwrap();
+        let region = matcher::variable_region(body);
+        assert!(matcher::in_lex_range(region, "C", "E"), "{}", region);
+    }
+}
+
+#[test]
+fn test_active_clock_excludes_suspension_gaps() {
+    let start = std::time::Instant::now();
+    let mut clock = ActiveClock::new(start, Duration::from_secs(2));
+
+    // Regular half-second samples are all counted
+    assert_eq!(
+        clock.sample(start + Duration::from_millis(500)),
+        Duration::from_millis(500)
+    );
+    assert_eq!(
+        clock.sample(start + Duration::from_secs(1)),
+        Duration::from_secs(1)
+    );
+
+    // A ten-minute jump (e.g. Ctrl-Z, then fg) is left out entirely
+    let resumed = start + Duration::from_secs(601);
+    assert_eq!(clock.sample(resumed), Duration::from_secs(1));
+
+    // Counting resumes normally afterwards
+    assert_eq!(
+        clock.sample(resumed + Duration::from_millis(500)),
+        Duration::from_millis(1500)
+    );
+    assert_eq!(clock.active(), Duration::from_millis(1500));
+}
+
+#[test]
+#[cfg(feature = "cli")]
+fn test_max_attempts_stops_search() {
+    let thread_count = 2;
+    let config = ThreadPoolConfig {
+        // The body always starts with "AAAA", so this never matches
+        pattern: "^zzzz".to_string(),
+        thread_count,
+        max_attempts: Some(500),
+        ..Default::default()
+    };
+
+    let metrics = run_search(config, &OutputOptions::default()).unwrap();
+
+    assert_eq!(metrics.matches_found, 0);
+    assert!(metrics.attempts >= 500);
+    // Overshoot is bounded by one 50-attempt batch per thread
+    assert!(metrics.attempts <= 500 + 50 * thread_count as u64);
+}
+
+#[test]
+#[cfg(feature = "cli")]
+fn test_timeout_stops_search() {
+    let config = ThreadPoolConfig {
+        pattern: "^zzzz".to_string(),
+        thread_count: 2,
+        timeout: Some(Duration::from_secs(1)),
+        ..Default::default()
+    };
+
+    let start = std::time::Instant::now();
+    let metrics = run_search(config, &OutputOptions::default()).unwrap();
+    let elapsed = start.elapsed();
+
+    assert!(elapsed >= Duration::from_secs(1));
+    assert!(elapsed < Duration::from_secs(2), "took {:?}", elapsed);
+    assert_eq!(metrics.matches_found, 0);
+    // The partial run is still reported
+    assert!(metrics.attempts > 0);
+}
+
+#[test]
+fn test_blocklist_rejects_key_body() {
+    let blocked =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIHm1Vi6P5lT5QHixFuCKi6eQH4U65pW+1+DjkQutBJZk x";
+    let clean =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIHm1Vi6P5lT5QHixEuipi6eQH4U65pW+1+DjkQutBJZk x";
+
+    assert!(matcher::ssh_key_contains_blocked_word(blocked).unwrap());
+    assert!(!matcher::ssh_key_contains_blocked_word(clean).unwrap());
+    // Only the key body is screened, not the comment
+    assert!(!matcher::ssh_key_contains_blocked_word(&clean.replace(" x", " fuck")).unwrap());
+}
+
+#[test]
+fn test_load_wordlist_skips_comments_and_rejects_empty_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let wordlist = dir.path().join("words.txt");
+
+    std::fs::write(&wordlist, "# names\nalice\n\n  bob  \n").unwrap();
+    assert_eq!(
+        matcher::load_wordlist(&wordlist).unwrap(),
+        vec!["alice".to_string(), "bob".to_string()]
+    );
+
+    std::fs::write(&wordlist, "# nothing here\n\n").unwrap();
+    assert!(matches!(
+        matcher::load_wordlist(&wordlist),
+        Err(vanityssh_rust::error::VanityError::InvalidFormat(_))
+    ));
+}
+
+#[test]
+#[cfg(feature = "cli")]
+fn test_safe_rejects_blocklisted_matches() {
+    // A seeded single-thread search generates the same keys both times
+    let config = ThreadPoolConfig {
+        pattern: "sex".to_string(),
+        thread_count: 1,
+        seed: Some([7u8; 32]),
+        max_attempts: Some(3000),
+        ..Default::default()
+    };
+
+    let unsafe_run = run_search(config.clone(), &OutputOptions::default()).unwrap();
+    assert_eq!(unsafe_run.matches_found, 1);
+
+    let safe_config = ThreadPoolConfig {
+        safe: true,
+        ..config
+    };
+    let safe_run = run_search(safe_config, &OutputOptions::default()).unwrap();
+    assert_eq!(safe_run.matches_found, 0);
+}
+
+/// Wraps the OS RNG and records which thread draws from it
+struct ThreadRecordingRng {
+    threads: Vec<std::thread::ThreadId>,
+}
+
+impl rand::RngCore for ThreadRecordingRng {
+    fn next_u32(&mut self) -> u32 {
+        self.threads.push(std::thread::current().id());
+        rand::rngs::OsRng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.threads.push(std::thread::current().id());
+        rand::rngs::OsRng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.threads.push(std::thread::current().id());
+        rand::rngs::OsRng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> std::result::Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl rand::CryptoRng for ThreadRecordingRng {}
+
+#[test]
+fn test_inline_search_runs_on_calling_thread() {
+    let config = ThreadPoolConfig {
+        pattern: ".*".to_string(),
+        ..Default::default()
+    };
+
+    let mut rng = ThreadRecordingRng {
+        threads: Vec::new(),
+    };
+    let key_match = find_key_inline_with(&config, &mut rng)
+        .unwrap()
+        .expect(".* matches the first key");
+
+    assert_eq!(key_match.attempts, 1);
+    assert_eq!(key_match.thread_id, 0);
+    assert!(!rng.threads.is_empty());
+    let current = std::thread::current().id();
+    assert!(rng.threads.iter().all(|id| *id == current));
+}
+
+#[test]
+fn test_inline_search_matches_seeded_pool() {
+    let config = ThreadPoolConfig {
+        pattern: "ab".to_string(),
+        thread_count: 1,
+        seed: Some([3u8; 32]),
+        ..Default::default()
+    };
+
+    let inline = find_key_inline(&config).unwrap().unwrap();
+    let pooled = collect_vanity_keys(config, 1).unwrap().remove(0);
+
+    assert_eq!(inline.public_key, pooled.public_key);
+    assert_eq!(inline.attempts, pooled.attempts);
+}
+
+#[test]
+fn test_assess_generated_key_is_healthy() {
+    let (public_key, _) = keygen::generate_openssh_key_pair(None).unwrap();
+    assert_eq!(
+        quality::assess_ssh_public_key(&public_key).unwrap(),
+        quality::KeyQuality::Healthy
+    );
+}
+
+#[test]
+fn test_assess_flags_degenerate_points() {
+    use ed25519_dalek::VerifyingKey;
+
+    // y = 1 encodes the identity point
+    let mut identity = [0u8; 32];
+    identity[0] = 1;
+    let identity = VerifyingKey::from_bytes(&identity).unwrap();
+    assert_eq!(
+        quality::assess_key(&identity),
+        quality::KeyQuality::SmallOrder
+    );
+
+    // y = 0 is a point of order 4
+    let zero = VerifyingKey::from_bytes(&[0u8; 32]).unwrap();
+    assert_eq!(quality::assess_key(&zero), quality::KeyQuality::AllZero);
+}
+
+#[test]
+fn test_estimate_difficulty_anchored_prefix() {
+    let expected = |pattern: &str, case_sensitive| {
+        matcher::estimate_difficulty(pattern, case_sensitive).map(|d| d.expected_attempts)
+    };
+    let header = "^AAAAC3NzaC1lZDI1NTE5AAAAI";
+
+    // The fixed header is free, in either case when case-insensitive
+    assert_eq!(expected("^AAAA", true), Some(1.0));
+    assert_eq!(expected("^aaaac3", false), Some(1.0));
+
+    // The first variable character is one of 16, the rest one of 64
+    let first = format!("{}B", header);
+    assert_eq!(expected(&first, true), Some(16.0));
+    let two = format!("{}Bx", header);
+    assert_eq!(expected(&two, true), Some(1024.0));
+    assert_eq!(expected(&two, false), Some(512.0));
+    let escaped = format!("{}B\\+", header);
+    assert_eq!(expected(&escaped, false), Some(1024.0));
+}
+
+#[test]
+fn test_estimate_difficulty_impossible_and_unsupported() {
+    let expected = |pattern: &str, case_sensitive| {
+        matcher::estimate_difficulty(pattern, case_sensitive).map(|d| d.expected_attempts)
+    };
+    // Contradicts the fixed header
+    assert_eq!(expected("^abc", true), Some(f64::INFINITY));
+    // The first variable character is never past 'P'
+    assert_eq!(
+        expected("^AAAAC3NzaC1lZDI1NTE5AAAAIZ", true),
+        Some(f64::INFINITY)
+    );
+
+    assert_eq!(expected("abc", false), None);
+    assert_eq!(expected("^ab.", false), None);
+    assert_eq!(expected("^a|b", false), None);
+}
+
+#[test]
+fn test_difficulty_formatting() {
+    let format = |expected: f64| {
+        let difficulty = matcher::Difficulty::from_expected_attempts(expected);
+        (difficulty.to_string(), difficulty.odds())
+    };
+
+    assert_eq!(format(1.0), ("1 attempt".into(), "1 in 1".into()));
+    assert_eq!(format(16.0), ("16 attempts".into(), "1 in 16".into()));
+    assert_eq!(format(1024.0), ("1.0K attempts".into(), "1 in 1.0K".into()));
+    assert_eq!(
+        format(16_777_216.0),
+        ("16.8M attempts".into(), "1 in 16.8M".into())
+    );
+    assert_eq!(
+        format(2f64.powi(40)),
+        ("1.1T attempts".into(), "1 in 1.1T".into())
+    );
+    assert_eq!(format(f64::INFINITY), ("impossible".into(), "never".into()));
+
+    let difficulty = matcher::Difficulty::from_expected_attempts(16.0);
+    assert_eq!(difficulty.per_key_probability, 1.0 / 16.0);
+    assert!(difficulty.is_possible());
+    let impossible = matcher::Difficulty::from_expected_attempts(f64::INFINITY);
+    assert_eq!(impossible.per_key_probability, 0.0);
+    assert!(!impossible.is_possible());
+}
+
+#[test]
+fn test_pattern_is_satisfiable() {
+    // Anchored base64 literals that fit the header
+    assert!(matcher::pattern_is_satisfiable(
+        "^AAAAC3NzaC1lZDI1NTE5AAAAIBx",
+        true
+    ));
+    assert!(matcher::pattern_is_satisfiable("^aaaac3", false));
+    assert!(matcher::pattern_is_satisfiable("cafe$", false));
+    assert!(matcher::pattern_is_satisfiable("ca\\+fe$", true));
+    // Only anchored literals are judged
+    assert!(matcher::pattern_is_satisfiable("cafe_", false));
+    assert!(matcher::pattern_is_satisfiable("^ca.e_", false));
+    assert!(matcher::pattern_is_satisfiable("^\\d_", false));
+}
+
+#[test]
+fn test_pattern_is_satisfiable_rejects_impossible_literals() {
+    // Characters base64 never produces, escaped or not
+    assert!(!matcher::pattern_is_satisfiable(
+        "^AAAAC3NzaC1lZDI1NTE5AAAAIcafe_",
+        false
+    ));
+    assert!(!matcher::pattern_is_satisfiable("cafe\\-babe$", false));
+    assert!(!matcher::pattern_is_satisfiable("^cafe=$", false));
+    // Contradicts the fixed header, or its first variable character
+    assert!(!matcher::pattern_is_satisfiable("^BBBB", false));
+    assert!(!matcher::pattern_is_satisfiable(
+        "^AAAAC3NzaC1lZDI1NTE5AAAAIZ",
+        true
+    ));
+    assert!(!matcher::pattern_is_satisfiable(
+        &format!("{}$", "A".repeat(69)),
+        false
+    ));
+
+    let reason = matcher::unsatisfiable_reason("cafe_$", false).unwrap();
+    assert!(reason.contains("'_'"), "{}", reason);
+    let reason = matcher::unsatisfiable_reason("^BBBB", false).unwrap();
+    assert!(reason.contains("header"), "{}", reason);
+}
+
+#[test]
+fn test_estimated_attempts_for_progress_bar() {
+    let config = ThreadPoolConfig {
+        pattern: "^AAAAC3NzaC1lZDI1NTE5AAAAIBx".to_string(),
+        case_sensitive: true,
+        ..Default::default()
+    };
+    assert_eq!(estimated_attempts(&config), Some(1024));
+
+    // Unestimable and impossible patterns fall back to the spinner
+    let unanchored = ThreadPoolConfig {
+        pattern: "cafe".to_string(),
+        ..config.clone()
+    };
+    assert_eq!(estimated_attempts(&unanchored), None);
+    let impossible = ThreadPoolConfig {
+        pattern: "^zzzz".to_string(),
+        ..config.clone()
+    };
+    assert_eq!(estimated_attempts(&impossible), None);
+
+    // So do searches the estimate doesn't describe
+    let several = ThreadPoolConfig {
+        extra_patterns: vec!["^AAAAC3NzaC1lZDI1NTE5AAAAIC".to_string()],
+        ..config.clone()
+    };
+    assert_eq!(estimated_attempts(&several), None);
+    let fingerprint = ThreadPoolConfig {
+        match_target: matcher::MatchTarget::Fingerprint,
+        ..config
+    };
+    assert_eq!(estimated_attempts(&fingerprint), None);
+}
+
+#[test]
+fn test_format_count_and_duration() {
+    assert_eq!(stats::format_count(950.0), "950");
+    assert_eq!(stats::format_count(16_700_000.0), "16.7M");
+    assert_eq!(stats::format_count(95_000.0), "95.0K");
+    assert_eq!(stats::format_duration(42.0), "42s");
+    assert_eq!(stats::format_duration(176.0), "3m");
+    assert_eq!(stats::format_duration(2.5 * 86400.0), "2.5 days");
+}
+
+#[test]
+fn test_same_seed_produces_identical_first_key() {
+    let config = ThreadPoolConfig {
+        pattern: ".*".to_string(),
+        thread_count: 1,
+        seed: Some(keygen::seed_from_u64(42)),
+        ..Default::default()
+    };
+
+    let first = collect_vanity_keys(config.clone(), 1).unwrap().remove(0);
+    let second = collect_vanity_keys(config, 1).unwrap().remove(0);
+
+    assert_eq!(first.public_key, second.public_key);
+    assert_eq!(first.private_key, second.private_key);
+}
+
+#[test]
+fn test_reproduce_matches_seeded_key_pair() {
+    let seed = keygen::seed_from_u64(7);
+    let config = ThreadPoolConfig {
+        pattern: "ab".to_string(),
+        thread_count: 1,
+        seed: Some(seed),
+        ..Default::default()
+    };
+
+    let found = collect_vanity_keys(config, 1).unwrap().remove(0);
+    let (public_key, private_key) =
+        keygen::reproduce_openssh_key_pair(&seed, found.thread_id, found.attempts, None).unwrap();
+
+    assert_eq!(public_key, found.public_key);
+    assert_eq!(private_key, found.private_key);
+}
+
+#[test]
+fn test_seeded_hex_key_pair_is_deterministic() {
+    let mut first = keygen::KeyRng::seeded(keygen::seed_from_u64(1));
+    let mut second = keygen::KeyRng::seeded(keygen::seed_from_u64(1));
+    let mut other = keygen::KeyRng::seeded(keygen::seed_from_u64(2));
+
+    let key = keygen::generate_key_pair_with(&mut first).unwrap();
+    assert_eq!(key, keygen::generate_key_pair_with(&mut second).unwrap());
+    assert_ne!(key, keygen::generate_key_pair_with(&mut other).unwrap());
+}
+
+#[test]
+#[cfg(feature = "cli")]
+fn test_vanity_search_builder_run() {
+    let metrics = VanitySearch::new(".*")
+        .threads(1)
+        .comment("builder@example")
+        .case_sensitive(true)
+        .run()
+        .unwrap();
+
+    assert_eq!(metrics.matches_found, 1);
+}
+
+#[test]
+fn test_vanity_search_callback_receives_single_match() {
+    let mut keys = Vec::new();
+    let metrics = VanitySearch::new(".*")
+        .threads(2)
+        .comment("builder@example")
+        .run_with_callback(|key_match| keys.push(key_match))
+        .unwrap();
+
+    assert_eq!(keys.len(), 1);
+    assert_eq!(metrics.matches_found, 1);
+    assert!(keys[0].public_key.ends_with(" builder@example"));
+}
+
+#[test]
+fn test_vanity_search_callback_streaming_until_limit() {
+    let mut seen = 0u64;
+    let metrics = VanitySearch::new(".*")
+        .threads(2)
+        .streaming(true)
+        .max_attempts(300)
+        .run_with_callback(|_| seen += 1)
+        .unwrap();
+
+    assert!(seen > 1);
+    assert_eq!(seen, metrics.matches_found);
+}
+
+#[test]
+fn test_vanity_search_max_rate_caps_keys_per_second() {
+    let metrics = VanitySearch::new("^zzzz")
+        .threads(2)
+        .max_rate(400.0)
+        .max_attempts(300)
+        .run_with_callback(|_| {})
+        .unwrap();
+
+    // 300 keys at 400 a second take about 0.75s; far fewer would mean the
+    // cap is off, and a slow machine can only make it lower
+    assert!(metrics.attempts >= 300);
+    assert!(
+        metrics.keys_per_second <= 400.0 * 1.15,
+        "{} keys/sec",
+        metrics.keys_per_second
+    );
+    assert!(
+        metrics.keys_per_second >= 400.0 * 0.5,
+        "{} keys/sec",
+        metrics.keys_per_second
+    );
+}
+
+#[test]
+fn test_vanity_search_cancel_token_stops_the_search() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    let token = Arc::new(AtomicBool::new(false));
+    let stopper = {
+        let token = Arc::clone(&token);
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(300));
+            token.store(true, Ordering::Relaxed);
+        })
+    };
+
+    // `^zzzz` never matches, so only the token can end this search
+    let start = Instant::now();
+    let metrics = VanitySearch::new("^zzzz")
+        .threads(2)
+        .cancel_token(Arc::clone(&token))
+        .run_with_callback(|_| panic!("no key should match"))
+        .unwrap();
+    stopper.join().unwrap();
+
+    assert!(start.elapsed() < Duration::from_secs(30));
+    assert!(metrics.attempts > 0);
+    assert_eq!(metrics.matches_found, 0);
+}
+
+#[test]
+fn test_vanity_search_count_stops_after_n_matches() {
+    let mut seen = 0u64;
+    let metrics = VanitySearch::new(".*")
+        .threads(2)
+        .count(3)
+        .run_with_callback(|_| seen += 1)
+        .unwrap();
+
+    assert_eq!(seen, 3);
+    assert_eq!(metrics.matches_found, 3);
+}
+
+#[test]
+fn test_vanity_search_rejects_invalid_pattern() {
+    assert!(VanitySearch::new("[").run_with_callback(|_| {}).is_err());
+}
+
+#[test]
+fn test_matching_keys_iterator() {
+    let keys: Vec<KeyMatch> = keygen::matching_keys(".*", false, Some("iter@example"))
+        .take(3)
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert_eq!(keys.len(), 3);
+    let attempts: Vec<u64> = keys.iter().map(|k| k.attempts).collect();
+    assert_eq!(attempts, vec![1, 2, 3]);
+    assert!(keys.iter().all(|k| k.public_key.ends_with(" iter@example")));
+
+    // Filtering further works like any iterator
+    let key = keygen::matching_keys("a", false, None)
+        .filter_map(Result::ok)
+        .find(|k| k.public_key.contains('b'))
+        .unwrap();
+    assert!(matcher::ssh_key_matches_pattern(&key.public_key, "a", false).unwrap());
+}
+
+#[test]
+fn test_matching_keys_invalid_pattern_yields_one_error() {
+    let mut keys = keygen::matching_keys("[", false, None);
+    assert!(keys.next().unwrap().is_err());
+    assert!(keys.next().is_none());
+}
+
+#[test]
+fn test_key_algorithm_parsing() {
+    use vanityssh_rust::keygen::KeyAlgorithm;
+
+    assert_eq!(
+        "ed25519".parse::<KeyAlgorithm>().unwrap(),
+        KeyAlgorithm::Ed25519
+    );
+    assert_eq!(
+        "rsa".parse::<KeyAlgorithm>().unwrap(),
+        KeyAlgorithm::Rsa {
+            bits: keygen::DEFAULT_RSA_BITS
+        }
+    );
+    let rsa4096 = "rsa:4096".parse::<KeyAlgorithm>().unwrap();
+    assert_eq!(rsa4096, KeyAlgorithm::Rsa { bits: 4096 });
+    assert_eq!(rsa4096.to_string(), "rsa:4096");
+    assert_eq!(rsa4096.key_type(), vanityssh_rust::ssh::KeyType::Rsa);
+
+    assert!("rsa:1024".parse::<KeyAlgorithm>().is_err());
+    assert!("rsa:big".parse::<KeyAlgorithm>().is_err());
+    assert!("dsa".parse::<KeyAlgorithm>().is_err());
+}
+
+#[test]
+fn test_encoded_key_matches() {
+    use matcher::Encoding;
+
+    // 0xff repeated encodes to a run of '7's in base32
+    let key = [0xffu8; 32];
+    assert_eq!(
+        &matcher::encode_key(&key, Encoding::Base32).unwrap()[..4],
+        "7777"
+    );
+    assert!(matcher::encoded_key_matches(&key, "^7777", Encoding::Base32, true).unwrap());
+    assert!(matcher::encoded_key_matches(&key, "^FFFF", Encoding::Hex, false).unwrap());
+    assert!(!matcher::encoded_key_matches(&key, "^FFFF", Encoding::Hex, true).unwrap());
+
+    // Base64 is the usual OpenSSH body, header included
+    let ssh_key = vanityssh_rust::ssh::encode_ssh_public_key(&key, None).unwrap();
+    let body = vanityssh_rust::ssh::public_key::extract_ssh_key_data(&ssh_key).unwrap();
+    assert_eq!(matcher::encode_key(&key, Encoding::Base64).unwrap(), body);
+
+    assert_eq!("base32".parse::<Encoding>().unwrap(), Encoding::Base32);
+    assert!("base58".parse::<Encoding>().is_err());
+}
+
+#[test]
+fn test_thread_pool_matches_hex_encoding() {
+    let config = ThreadPoolConfig {
+        pattern: "^ab".to_string(),
+        thread_count: 1,
+        encoding: matcher::Encoding::Hex,
+        ..Default::default()
+    };
+
+    let key_match = find_key_inline(&config).unwrap().unwrap();
+    let raw =
+        vanityssh_rust::ssh::public_key::decode_ssh_public_key(&key_match.public_key).unwrap();
+    assert!(hex::encode(raw).starts_with("ab"));
+}
+
+#[test]
+fn test_match_scope_full_includes_key_type() {
+    use matcher::MatchScope;
+
+    let (public_key, _) = keygen::generate_openssh_key_pair(Some("me@example.com")).unwrap();
+    let full = matcher::scoped_text(&public_key, MatchScope::Full).unwrap();
+    assert!(full.starts_with("ssh-ed25519 AAAAC3"));
+    assert!(!full.contains("me@example.com"));
+
+    let pattern = "^ssh-ed25519 AAAA";
+    assert!(
+        matcher::ssh_key_matches_pattern_in(&public_key, pattern, true, MatchScope::Full).unwrap()
+    );
+    assert!(!matcher::ssh_key_matches_pattern(&public_key, pattern, true).unwrap());
+
+    assert_eq!("full".parse::<MatchScope>().unwrap(), MatchScope::Full);
+    assert!("comment".parse::<MatchScope>().is_err());
+}
+
+#[test]
+fn test_thread_pool_matches_full_scope() {
+    let config = ThreadPoolConfig {
+        pattern: "^ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAI[A-D]".to_string(),
+        thread_count: 1,
+        case_sensitive: true,
+        match_scope: matcher::MatchScope::Full,
+        ..Default::default()
+    };
+
+    let key_match = find_key_inline(&config).unwrap().unwrap();
+    let body =
+        vanityssh_rust::ssh::public_key::extract_ssh_key_data(&key_match.public_key).unwrap();
+    assert!(matches!(body.as_bytes()[25], b'A'..=b'D'));
+}
+
+#[test]
+fn test_truncate_text_boundaries() {
+    assert_eq!(matcher::truncate_text("abcdef", None), "abcdef");
+    assert_eq!(matcher::truncate_text("abcdef", Some(3)), "abc");
+    assert_eq!(matcher::truncate_text("abcdef", Some(6)), "abcdef");
+    assert_eq!(matcher::truncate_text("abcdef", Some(100)), "abcdef");
+    assert_eq!(matcher::truncate_text("abcdef", Some(0)), "");
+}
+
+#[test]
+fn test_match_length_limits_where_patterns_match() {
+    let (public_key, _) = keygen::generate_openssh_key_pair(None).unwrap();
+    let body = vanityssh_rust::ssh::public_key::extract_ssh_key_data(&public_key).unwrap();
+    let matches = |pattern: &str, length: usize| {
+        matcher::ssh_key_matches_pattern_truncated(&public_key, pattern, true, length).unwrap()
+    };
+
+    // Characters 27 to 30 only match once the cut includes all of them
+    let window = regex::escape(&body[26..30]);
+    assert!(!matches(&window, 29));
+    assert!(matches(&window, 30));
+
+    // `$` anchors at the cut rather than at the end of the body
+    let at_cut = format!("{}$", regex::escape(&body[28..30]));
+    assert!(matches(&at_cut, 30));
+    assert!(!matches(&at_cut, 31));
+
+    // A cut past the end of the body matches the whole body
+    let end = format!("{}$", regex::escape(&body[body.len() - 4..]));
+    assert!(matches(&end, body.len()));
+    assert!(matches(&end, body.len() + 10));
+}
+
+#[test]
+fn test_thread_pool_match_length() {
+    // Without the cut `B` would match almost any key somewhere in the body
+    let config = ThreadPoolConfig {
+        pattern: "B".to_string(),
+        thread_count: 1,
+        case_sensitive: true,
+        match_length: Some(27),
+        seed: Some([7; 32]),
+        ..Default::default()
+    };
+
+    let key_match = find_key_inline(&config).unwrap().unwrap();
+    let body =
+        vanityssh_rust::ssh::public_key::extract_ssh_key_data(&key_match.public_key).unwrap();
+    assert!(body[25..27].contains('B'));
+}
+
+#[test]
+fn test_case_insensitive_warning() {
+    assert!(matcher::case_insensitive_warning("abc", false).is_some());
+    assert!(matcher::case_insensitive_warning("(?i)abc", false).is_some());
+    assert!(matcher::case_insensitive_warning("abc", true).is_none());
+
+    // Nothing to fold in digits, symbols or escapes
+    assert!(matcher::case_insensitive_warning("^12+/", false).is_none());
+    assert!(matcher::case_insensitive_warning(r"\d\d", false).is_none());
+}
+
+#[test]
+fn test_instantaneous_rate_tracks_recent_slowdown() {
+    let mut metrics = PerformanceMetrics::new();
+    assert_eq!(metrics.instantaneous_rate(), 0.0);
+
+    // 1000 keys/sec for 10 seconds, then 100 keys/sec for 10 more
+    let mut attempts = 0;
+    for second in 1..=20 {
+        attempts += if second <= 10 { 1000 } else { 100 };
+        metrics.update(attempts, 0, Duration::from_secs(second));
+    }
+
+    assert!((metrics.instantaneous_rate() - 100.0).abs() < 1e-9);
+    assert!((metrics.keys_per_second - 550.0).abs() < 1e-9);
+
+    // Display keeps showing the lifetime figure
+    assert!(metrics.to_string().contains("Speed: 550.00 keys/sec"));
+}
+
+#[test]
+fn test_per_thread_breakdown() {
+    let mut metrics = PerformanceMetrics::new();
+    metrics.record_thread_attempts(1, 300);
+    metrics.record_thread_attempts(0, 1_200_000);
+    metrics.record_thread_attempts(1, 50);
+
+    assert_eq!(metrics.per_thread, vec![1_200_000, 350]);
+    assert_eq!(
+        metrics.per_thread_breakdown(),
+        "thread 0: 1.2M, thread 1: 350"
+    );
+}
+
+#[test]
+fn test_vanity_search_reports_per_thread_attempts() {
+    let metrics = VanitySearch::new(".*")
+        .threads(2)
+        .streaming(true)
+        .max_attempts(500)
+        .run_with_callback(|_| {})
+        .unwrap();
+
+    assert_eq!(metrics.per_thread.len(), 2);
+    assert_eq!(metrics.per_thread.iter().sum::<u64>(), metrics.attempts);
+}
+
+#[test]
+fn test_anchored_literal_fast_path_agrees_with_regex() {
+    let patterns = [
+        "^",
+        "^A",
+        "^AAAAC3",
+        "^aaaac3",
+        "^AAAAC3NzaC1lZDI1NTE5AAAAI",
+        "^AAAAC3X",
+        "(?i)^aaaa",
+        r"^AAAA\+",
+        r"^AAAA\/",
+        "^AAAA/",
+    ];
+
+    for _ in 0..50 {
+        let (public_key, _) = keygen::generate_openssh_key_pair(None).unwrap();
+        let body = vanityssh_rust::ssh::public_key::extract_ssh_key_data(&public_key).unwrap();
+        let own_prefix = format!("^{}", &body[..28]);
+        let flipped_prefix = format!("^{}", body[..28].to_ascii_lowercase());
+
+        for pattern in patterns
+            .iter()
+            .copied()
+            .chain([own_prefix.as_str(), flipped_prefix.as_str()])
+        {
+            for case_sensitive in [true, false] {
+                let regex = regex::Regex::new(&matcher::effective_pattern(pattern, case_sensitive))
+                    .unwrap();
+                assert_eq!(
+                    matcher::matches_pattern(&body, pattern, case_sensitive).unwrap(),
+                    regex.is_match(&body),
+                    "pattern {} (case sensitive: {}) on {}",
+                    pattern,
+                    case_sensitive,
+                    body
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_matches_compiled_agrees_with_matches_pattern() {
+    let patterns = ["abc", "(?i)ABC", "^AAAAC3", "[0-9]{2}$", "(?P<word>cafe)"];
+    for _ in 0..20 {
+        let (public_key, _) = keygen::generate_openssh_key_pair(None).unwrap();
+        let body = vanityssh_rust::ssh::public_key::extract_ssh_key_data(&public_key).unwrap();
+        for pattern in patterns {
+            for case_sensitive in [true, false] {
+                let regex = matcher::compile_pattern(pattern, case_sensitive).unwrap();
+                assert_eq!(
+                    matcher::matches_compiled(&body, &regex),
+                    matcher::matches_pattern(&body, pattern, case_sensitive).unwrap()
+                );
+            }
+        }
+    }
+    assert!(matcher::compile_pattern("[unclosed", false).is_err());
+}
+
+#[test]
+fn test_os_seeded_rngs_are_independent() {
+    let mut first = keygen::KeyRng::os_seeded();
+    let mut second = keygen::KeyRng::os_seeded();
+
+    let (first_key, _) = keygen::generate_key_pair_with(&mut first).unwrap();
+    let (second_key, _) = keygen::generate_key_pair_with(&mut second).unwrap();
+    assert_ne!(first_key, second_key);
+}
+
+#[test]
+fn test_save_key_pair_writes_complete_files_atomically() {
+    let (public_key, private_key) = keygen::generate_openssh_key_pair(Some("atomic")).unwrap();
+    let key_match = KeyMatch {
+        public_key: public_key.clone(),
+        private_key: private_key.clone(),
+        attempts: 1,
+        total_attempts: 1,
+        thread_id: 0,
+        captures: vec![],
+        matched_pattern: String::new(),
+        exact_case: false,
+        public_key_bytes: None,
+        private_key_bytes: None,
+    };
+
+    let dir = tempfile::tempdir().unwrap();
+    let (index, private_path, public_path) =
+        output::save_key_pair(dir.path(), 1, &key_match, true).unwrap();
+    assert_eq!(index, 1);
+
+    let saved = std::fs::read_to_string(&private_path).unwrap();
+    assert_eq!(saved, format!("{}\n", private_key));
+    assert_eq!(
+        std::fs::read_to_string(&public_path).unwrap(),
+        format!("{}\n", public_key)
+    );
+
+    // No temporary files are left behind next to the keys
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&private_path)
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
+
+#[test]
+fn test_affix_pattern_prefix_only() {
+    let pattern = matcher::affix_pattern(Some("AAAAC3NzaC1lZDI1NTE5AAAAIab+"), None);
+    assert_eq!(pattern, r"^AAAAC3NzaC1lZDI1NTE5AAAAIab\+");
+
+    let key = "AAAAC3NzaC1lZDI1NTE5AAAAIab+xyz";
+    assert!(matcher::matches_pattern(key, &pattern, true).unwrap());
+    assert!(matcher::matches_pattern(&key.to_lowercase(), &pattern, false).unwrap());
+    assert!(!matcher::matches_pattern(&key.to_lowercase(), &pattern, true).unwrap());
+}
+
+#[test]
+fn test_affix_pattern_suffix_only() {
+    let pattern = matcher::affix_pattern(None, Some("xyz"));
+    assert_eq!(pattern, "xyz$");
+
+    assert!(matcher::matches_pattern("AAAAxyz", &pattern, true).unwrap());
+    assert!(matcher::matches_pattern("AAAAXYZ", &pattern, false).unwrap());
+    assert!(!matcher::matches_pattern("AAAAxyzA", &pattern, true).unwrap());
+}
+
+#[test]
+fn test_affix_pattern_prefix_and_suffix() {
+    let pattern = matcher::affix_pattern(Some("ab"), Some("ba"));
+    assert_eq!(pattern, "^ab.*ba$");
+
+    for (key, expected) in [
+        ("abba", true),
+        ("abXYZba", true),
+        ("abXYZ", false),
+        ("XYZba", false),
+        // The prefix and suffix can't share characters
+        ("aba", false),
+    ] {
+        for case_sensitive in [true, false] {
+            let regex = matcher::compile_pattern(&pattern, case_sensitive).unwrap();
+            assert_eq!(matcher::matches_compiled(key, &regex), expected, "{}", key);
+            assert_eq!(
+                matcher::matches_pattern(key, &pattern, case_sensitive).unwrap(),
+                expected,
+                "{}",
+                key
+            );
+        }
+    }
+
+    // Both must hold in a real search too
+    let config = ThreadPoolConfig {
+        pattern: matcher::affix_pattern(Some("AAAAC3NzaC1lZDI1NTE5AAAAIA"), Some("A")),
+        thread_count: 1,
+        case_sensitive: true,
+        ..Default::default()
+    };
+    let key_match = find_key_inline(&config).unwrap().unwrap();
+    let body =
+        vanityssh_rust::ssh::public_key::extract_ssh_key_data(&key_match.public_key).unwrap();
+    assert!(body.starts_with("AAAAC3NzaC1lZDI1NTE5AAAAIA"));
+    assert!(body.ends_with('A'));
+}
+
+#[test]
+fn test_stream_with_handlers_reports_matches_and_progress() {
+    let config = ThreadPoolConfig {
+        pattern: ".*".to_string(),
+        thread_count: 1,
+        ..Default::default()
+    };
+    let mut matches = Vec::new();
+    let metrics = stream_with_handlers(
+        config,
+        |key_match| matches.push(key_match.public_key.clone()),
+        |_| {},
+    )
+    .unwrap();
+    assert_eq!(matches.len(), 1);
+    assert_eq!(metrics.matches_found, 1);
+
+    // A search that never matches still reports progress until it times out
+    let config = ThreadPoolConfig {
+        pattern: "^zzzz".to_string(),
+        thread_count: 1,
+        timeout: Some(Duration::from_millis(1200)),
+        ..Default::default()
+    };
+    let mut reported = Vec::new();
+    let metrics = stream_with_handlers(
+        config,
+        |_| panic!("nothing should match"),
+        |metrics| reported.push(metrics.attempts),
+    )
+    .unwrap();
+    assert!(!reported.is_empty());
+    assert!(reported.windows(2).all(|pair| pair[0] <= pair[1]));
+    assert!(metrics.attempts >= *reported.last().unwrap());
+}
+
+#[test]
+fn test_search_state_round_trips_and_accumulates() {
+    use vanityssh_rust::state::SearchState;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state.json");
+    assert_eq!(SearchState::load(&path).unwrap(), SearchState::default());
+
+    let mut metrics = PerformanceMetrics::new();
+    metrics.update(1000, 2, Duration::from_secs(4));
+    let state = SearchState::default().with_run(&metrics, Duration::from_secs(5));
+    state.write(&path).unwrap();
+    assert_eq!(SearchState::load(&path).unwrap(), state);
+
+    let next = state.with_run(&metrics, Duration::from_secs(5));
+    assert_eq!(next.total_attempts, 2000);
+    assert_eq!(next.matches_found, 4);
+    assert_eq!(next.wall_time, 10.0);
+
+    std::fs::write(&path, "not json").unwrap();
+    assert!(SearchState::load(&path).is_err());
+}
+
+#[test]
+#[cfg(feature = "cli")]
+fn test_stream_openssh_keys_and_match_mt_rejects_zero_threads() {
+    let result = vanityssh_rust::stream_openssh_keys_and_match_mt("a", false, None, false, Some(0));
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_performance_metrics_render_formats() {
+    use vanityssh_rust::OutputFormat;
+
+    let mut metrics = PerformanceMetrics::new();
+    metrics.update(1000, 2, Duration::from_millis(4000));
+    metrics.per_thread = vec![600, 400];
+
+    assert_eq!(
+        metrics.render(OutputFormat::Text),
+        "Attempts: 1000 | Matches: 2 | Duration: 4.00s | Speed: 250.00 keys/sec"
+    );
+    assert_eq!(metrics.render(OutputFormat::Text), metrics.to_string());
+    assert_eq!(
+        metrics.render(OutputFormat::Json),
+        r#"{"attempts":1000,"matches_found":2,"duration_secs":4.0,"keys_per_second":250.0,"per_thread":[600,400],"near_misses":0}"#
+    );
+    assert_eq!(
+        metrics.render(OutputFormat::Csv),
+        "attempts,matches,duration_s,keys_per_sec\n1000,2,4.000,250.00"
+    );
+
+    assert_eq!("csv".parse::<OutputFormat>().unwrap(), OutputFormat::Csv);
+    assert!("xml".parse::<OutputFormat>().is_err());
+}
@@ -1,7 +1,14 @@
 // tests/thread_pool_tests.rs
 // Created: 2025-04-22 14:30:00 by kengggg
 
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
+use vanityssh_rust::cores::{
+    effective_thread_count, rank_cores, CoreRate, ThreadWarning, MAX_THREADS,
+};
+use vanityssh_rust::keygen;
+use vanityssh_rust::stream_with_handlers;
 use vanityssh_rust::thread_pool::{run_thread_pool, ThreadPoolConfig};
 
 #[test]
@@ -13,21 +20,29 @@ fn test_thread_pool_basic() {
         case_sensitive: false,
         streaming: false,
         comment: None,
+        ..Default::default()
     };
 
     // Run the thread pool
-    let (match_receiver, _status_receiver) = run_thread_pool(config).unwrap();
+    let handle = run_thread_pool(config).unwrap();
 
     // Wait for a match
-    let key_match = match_receiver
+    let key_match = handle
+        .match_receiver
         .recv_timeout(Duration::from_secs(10))
         .unwrap();
 
+    // Without streaming the workers stop after the match, closing the status
+    // channel once they have reported everything
+    let reported: u64 = handle.status_receiver.iter().map(|s| s.attempts).sum();
+    handle.shutdown();
+
     // Verify we got a valid key match
     assert!(!key_match.public_key.is_empty());
     assert!(!key_match.private_key.is_empty());
     assert!(key_match.attempts > 0);
     assert!(key_match.thread_id < 2); // Should be thread 0 or 1
+    assert!(reported >= key_match.attempts);
 }
 
 #[test]
@@ -39,10 +54,11 @@ fn test_thread_pool_streaming() {
         case_sensitive: false,
         streaming: true, // Streaming mode
         comment: None,
+        ..Default::default()
     };
 
     // Run the thread pool
-    let (match_receiver, _status_receiver) = run_thread_pool(config).unwrap();
+    let handle = run_thread_pool(config).unwrap();
 
     // Get multiple matches
     let mut matches = 0;
@@ -50,11 +66,547 @@ fn test_thread_pool_streaming() {
 
     // Get up to 3 matches or timeout after 10 seconds
     while matches < 3 && start.elapsed() < Duration::from_secs(10) {
-        if match_receiver.recv_timeout(Duration::from_secs(1)).is_ok() {
+        if handle
+            .match_receiver
+            .recv_timeout(Duration::from_secs(1))
+            .is_ok()
+        {
             matches += 1;
         }
     }
+    handle.shutdown();
 
     // Should get at least one match
     assert!(matches > 0);
 }
+
+#[test]
+fn test_thread_pool_shutdown_joins_workers() {
+    let config = ThreadPoolConfig {
+        pattern: ".*".to_string(),
+        thread_count: 2,
+        case_sensitive: false,
+        streaming: true,
+        comment: None,
+        ..Default::default()
+    };
+
+    let handle = run_thread_pool(config).unwrap();
+    let terminate = handle.terminate_flag();
+
+    // Let the workers produce at least one match before stopping them
+    assert!(handle
+        .match_receiver
+        .recv_timeout(Duration::from_secs(10))
+        .is_ok());
+
+    handle.shutdown();
+
+    // Every worker held a clone of the flag; only ours is left once they exit
+    assert_eq!(Arc::strong_count(&terminate), 1);
+}
+
+#[test]
+fn test_thread_pool_staggered_workers_distinct_first_keys() {
+    let thread_count = 4;
+    let config = ThreadPoolConfig {
+        pattern: ".*".to_string(), // Every candidate matches, so the first match is the first key
+        thread_count,
+        streaming: true,
+        startup_stagger: Some(Duration::from_millis(2)),
+        ..Default::default()
+    };
+
+    let handle = run_thread_pool(config).unwrap();
+
+    // Record the first key produced by each worker
+    let mut first_keys = HashMap::new();
+    let start = std::time::Instant::now();
+    while first_keys.len() < thread_count && start.elapsed() < Duration::from_secs(10) {
+        if let Ok(key_match) = handle.match_receiver.recv_timeout(Duration::from_secs(1)) {
+            first_keys
+                .entry(key_match.thread_id)
+                .or_insert(key_match.public_key);
+        }
+    }
+    handle.shutdown();
+
+    assert_eq!(first_keys.len(), thread_count);
+
+    let mut keys: Vec<&String> = first_keys.values().collect();
+    keys.sort();
+    keys.dedup();
+    assert_eq!(
+        keys.len(),
+        thread_count,
+        "No two workers may share a first key"
+    );
+}
+
+#[test]
+fn test_thread_pool_streaming_survives_full_match_channel() {
+    let config = ThreadPoolConfig {
+        pattern: ".*".to_string(), // Every key matches, so the match channel fills instantly
+        thread_count: 2,
+        streaming: true,
+        drop_when_full: true,
+        ..Default::default()
+    };
+
+    let handle = run_thread_pool(config).unwrap();
+
+    // Act as a consumer that never reads matches, only progress
+    let mut attempts = 0;
+    let start = std::time::Instant::now();
+    while start.elapsed() < Duration::from_millis(500) {
+        if let Ok(status) = handle
+            .status_receiver
+            .recv_timeout(Duration::from_millis(50))
+        {
+            attempts += status.attempts;
+        }
+    }
+
+    // Workers kept generating long after the 32-slot match channel filled up
+    assert!(attempts > 200, "only {} attempts were made", attempts);
+
+    // And matches are still delivered once the consumer catches up
+    assert!(handle
+        .match_receiver
+        .recv_timeout(Duration::from_secs(1))
+        .is_ok());
+
+    handle.shutdown();
+}
+
+#[test]
+fn test_rank_cores_orders_by_rate() {
+    let rates = vec![
+        CoreRate {
+            core: 0,
+            keys_per_second: 1200.0,
+        },
+        CoreRate {
+            core: 1,
+            keys_per_second: 3400.0,
+        },
+        CoreRate {
+            core: 2,
+            keys_per_second: 800.0,
+        },
+        CoreRate {
+            core: 3,
+            keys_per_second: 3100.0,
+        },
+    ];
+
+    let ranked: Vec<usize> = rank_cores(rates).iter().map(|rate| rate.core).collect();
+    assert_eq!(ranked, vec![1, 3, 0, 2]);
+}
+
+#[test]
+fn test_effective_thread_count_warns_and_clamps() {
+    // Up to four threads per CPU is fine
+    assert_eq!(effective_thread_count(4, 4), (4, None));
+    assert_eq!(effective_thread_count(16, 4), (16, None));
+
+    // Beyond that the count is kept but warned about
+    assert_eq!(
+        effective_thread_count(17, 4),
+        (17, Some(ThreadWarning::Oversubscribed))
+    );
+    assert_eq!(
+        effective_thread_count(5, 0),
+        (5, Some(ThreadWarning::Oversubscribed))
+    );
+
+    // Absurd counts are clamped
+    assert_eq!(
+        effective_thread_count(MAX_THREADS + 1, 4),
+        (MAX_THREADS, Some(ThreadWarning::Clamped))
+    );
+    assert_eq!(
+        effective_thread_count(usize::MAX, 8192),
+        (MAX_THREADS, Some(ThreadWarning::Clamped))
+    );
+}
+
+#[test]
+fn test_run_thread_pool_rejects_invalid_pattern() {
+    let config = ThreadPoolConfig {
+        pattern: "[unclosed".to_string(),
+        thread_count: 1,
+        ..Default::default()
+    };
+
+    assert!(matches!(
+        run_thread_pool(config),
+        Err(vanityssh_rust::error::VanityError::InvalidRegex(_))
+    ));
+}
+
+#[test]
+fn test_report_interval_sets_status_batch_size() {
+    let config = ThreadPoolConfig {
+        pattern: "^zzzz".to_string(),
+        thread_count: 1,
+        max_attempts: Some(100),
+        report_interval: Some(10),
+        ..Default::default()
+    };
+
+    let handle = run_thread_pool(config).unwrap();
+    let batches: Vec<u64> = handle.status_receiver.iter().map(|s| s.attempts).collect();
+    handle.shutdown();
+
+    assert_eq!(batches.iter().sum::<u64>(), 100);
+    assert!(batches.iter().all(|&attempts| attempts == 10));
+}
+
+#[test]
+fn test_smaller_batches_report_more_often() {
+    let updates_in = |report_interval: u64| {
+        let config = ThreadPoolConfig {
+            pattern: "^zzzz".to_string(),
+            thread_count: 1,
+            report_interval: Some(report_interval),
+            status_channel_capacity: 4096,
+            ..Default::default()
+        };
+        let handle = run_thread_pool(config).unwrap();
+        std::thread::sleep(Duration::from_millis(300));
+        let updates = handle.status_receiver.try_iter().count();
+        handle.shutdown();
+        updates
+    };
+
+    let small = updates_in(2);
+    let large = updates_in(1_000_000);
+    assert!(small > 10, "{} updates", small);
+    assert_eq!(large, 0);
+}
+
+#[test]
+fn test_match_channel_capacity_limits_waiting_matches() {
+    let config = ThreadPoolConfig {
+        pattern: ".*".to_string(),
+        thread_count: 1,
+        streaming: true,
+        match_channel_capacity: 2,
+        ..Default::default()
+    };
+
+    let handle = run_thread_pool(config).unwrap();
+    std::thread::sleep(Duration::from_millis(200));
+    // The worker blocks on the third match until one is received
+    assert_eq!(handle.match_receiver.len(), 2);
+    handle.shutdown();
+}
+
+#[test]
+fn test_shutdown_returns_exact_attempt_count() {
+    let config = ThreadPoolConfig {
+        pattern: "^zzzz".to_string(),
+        thread_count: 2,
+        max_attempts: Some(95),
+        report_interval: Some(10),
+        ..Default::default()
+    };
+
+    let handle = run_thread_pool(config).unwrap();
+    let reported: u64 = handle.status_receiver.iter().map(|s| s.attempts).sum();
+    let attempts = handle.shutdown();
+
+    assert_eq!(attempts, reported);
+    assert!((95..=115).contains(&attempts), "{}", attempts);
+}
+
+#[test]
+fn test_attempt_count_includes_the_matching_key() {
+    let config = ThreadPoolConfig {
+        pattern: ".*".to_string(),
+        thread_count: 1,
+        ..Default::default()
+    };
+
+    let handle = run_thread_pool(config).unwrap();
+    let key_match = handle
+        .match_receiver
+        .recv_timeout(Duration::from_secs(10))
+        .unwrap();
+    let attempts = handle.shutdown();
+
+    // The first key matches, and nothing is counted twice
+    assert_eq!(key_match.attempts, 1);
+    assert_eq!(key_match.total_attempts, 1);
+    assert_eq!(attempts, 1);
+}
+
+#[test]
+fn test_key_match_records_global_attempts() {
+    let config = ThreadPoolConfig {
+        pattern: ".*".to_string(),
+        thread_count: 2,
+        streaming: true,
+        ..Default::default()
+    };
+
+    let handle = run_thread_pool(config).unwrap();
+    let matches: Vec<_> = (0..20)
+        .map(|_| {
+            handle
+                .match_receiver
+                .recv_timeout(Duration::from_secs(10))
+                .unwrap()
+        })
+        .collect();
+    let attempts = handle.shutdown();
+
+    for key_match in &matches {
+        assert!(key_match.total_attempts >= key_match.attempts);
+        assert!(key_match.total_attempts <= attempts);
+    }
+    // Every key matches, so each match's global count is distinct
+    let mut totals: Vec<u64> = matches.iter().map(|m| m.total_attempts).collect();
+    totals.sort_unstable();
+    totals.dedup();
+    assert_eq!(totals.len(), matches.len());
+}
+
+#[test]
+fn test_thread_pool_reports_which_of_several_patterns_matched() {
+    let config = ThreadPoolConfig {
+        pattern: "^zzzz".to_string(),
+        extra_patterns: vec!["^yyyy".to_string(), ".*".to_string()],
+        thread_count: 1,
+        ..Default::default()
+    };
+
+    let handle = run_thread_pool(config).unwrap();
+    let key_match = handle
+        .match_receiver
+        .recv_timeout(Duration::from_secs(10))
+        .unwrap();
+    handle.shutdown();
+
+    assert_eq!(key_match.matched_pattern, ".*");
+}
+
+#[test]
+fn test_thread_pool_reports_first_of_several_literal_prefixes() {
+    // Every pattern is a plain prefix, so no regex runs; the first one that
+    // matches is reported, like with a regex set
+    let config = ThreadPoolConfig {
+        pattern: "^zzzz".to_string(),
+        extra_patterns: vec!["^AAAAC3NzaC1lZDI1NTE5".to_string(), "^AAAA".to_string()],
+        thread_count: 1,
+        ..Default::default()
+    };
+
+    let handle = run_thread_pool(config).unwrap();
+    let key_match = handle
+        .match_receiver
+        .recv_timeout(Duration::from_secs(10))
+        .unwrap();
+    handle.shutdown();
+
+    assert_eq!(key_match.matched_pattern, "^AAAAC3NzaC1lZDI1NTE5");
+}
+
+#[test]
+fn test_thread_pool_literal_prefix_skips_encoding_correctly() {
+    // A plain prefix is first checked on the raw key bytes. The character
+    // after the header is always 'A'..='P', so this prefix matches about
+    // one key in 16 case-insensitively and none case-sensitively.
+    let prefix = "^aaaac3nzac1lzdi1nte5aaaaia";
+    let config = ThreadPoolConfig {
+        pattern: prefix.to_string(),
+        thread_count: 1,
+        streaming: true,
+        max_matches: Some(3),
+        ..Default::default()
+    };
+    let mut bodies = Vec::new();
+    stream_with_handlers(
+        config.clone(),
+        |key_match| bodies.push(key_match.public_key.clone()),
+        |_| {},
+    )
+    .unwrap();
+    assert_eq!(bodies.len(), 3);
+    for public_key in bodies {
+        let body = public_key.split(' ').nth(1).unwrap();
+        assert!(body[..prefix.len() - 1].eq_ignore_ascii_case(&prefix[1..]));
+    }
+
+    let exact = ThreadPoolConfig {
+        case_sensitive: true,
+        streaming: false,
+        max_matches: None,
+        max_attempts: Some(500),
+        ..config
+    };
+    let metrics = stream_with_handlers(exact, |_| panic!("no key can match"), |_| {}).unwrap();
+    assert_eq!(metrics.matches_found, 0);
+}
+
+#[test]
+fn test_prefer_exact_rechecks_case() {
+    // A single lowercase letter: about half the case-insensitive hits show
+    // it in upper case
+    let config = ThreadPoolConfig {
+        pattern: "^AAAAC3NzaC1lZDI1NTE5AAAAI.[a-z]".to_string(),
+        thread_count: 1,
+        streaming: true,
+        prefer_exact: true,
+        seed: Some(keygen::seed_from_u64(7)),
+        ..Default::default()
+    };
+
+    let handle = run_thread_pool(config).unwrap();
+    let matches: Vec<_> = (0..40)
+        .map(|_| {
+            handle
+                .match_receiver
+                .recv_timeout(Duration::from_secs(10))
+                .unwrap()
+        })
+        .collect();
+    handle.shutdown();
+
+    for key_match in &matches {
+        let letter = key_match.public_key.as_bytes()["ssh-ed25519 ".len() + 26];
+        assert!(letter.is_ascii_alphabetic());
+        assert_eq!(key_match.exact_case, letter.is_ascii_lowercase());
+    }
+    assert!(matches.iter().any(|m| m.exact_case));
+    assert!(matches.iter().any(|m| !m.exact_case));
+}
+
+#[test]
+fn test_case_sensitive_matches_are_exact() {
+    let config = ThreadPoolConfig {
+        pattern: ".*".to_string(),
+        thread_count: 1,
+        case_sensitive: true,
+        ..Default::default()
+    };
+
+    let handle = run_thread_pool(config).unwrap();
+    let key_match = handle
+        .match_receiver
+        .recv_timeout(Duration::from_secs(10))
+        .unwrap();
+    handle.shutdown();
+
+    assert!(key_match.exact_case);
+}
+
+#[test]
+fn test_match_everything_patterns_take_the_fast_path() {
+    use vanityssh_rust::matcher;
+
+    for pattern in [".*", "", "(?i).*", "^.*$"] {
+        assert!(matcher::matches_everything(pattern), "{:?}", pattern);
+    }
+    for pattern in ["^A", ".", ".+", "(?P<all>.*)"] {
+        assert!(!matcher::matches_everything(pattern), "{:?}", pattern);
+    }
+
+    // Every body starts with 'A', so "^A" goes through the regex and still
+    // accepts the same keys
+    let first_keys = |pattern: &str| {
+        let config = ThreadPoolConfig {
+            pattern: pattern.to_string(),
+            thread_count: 1,
+            streaming: true,
+            seed: Some(keygen::seed_from_u64(11)),
+            ..Default::default()
+        };
+        let handle = run_thread_pool(config).unwrap();
+        let keys: Vec<(String, u64, bool)> = (0..5)
+            .map(|_| {
+                let key_match = handle
+                    .match_receiver
+                    .recv_timeout(Duration::from_secs(10))
+                    .unwrap();
+                assert!(key_match.captures.is_empty());
+                (
+                    key_match.public_key,
+                    key_match.attempts,
+                    key_match.exact_case,
+                )
+            })
+            .collect();
+        handle.shutdown();
+        keys
+    };
+
+    let expected = first_keys("^A");
+    assert_eq!(
+        expected.iter().map(|k| k.1).collect::<Vec<_>>(),
+        [1, 2, 3, 4, 5]
+    );
+    for pattern in [".*", "", "(?i).*"] {
+        assert_eq!(first_keys(pattern), expected, "{:?}", pattern);
+    }
+}
+
+#[test]
+fn test_near_pattern_counts_near_misses() {
+    // No body starts with `z`, so every key is a near miss of `^`
+    let metrics = stream_with_handlers(
+        ThreadPoolConfig {
+            pattern: "^z".to_string(),
+            near_pattern: Some("^".to_string()),
+            thread_count: 1,
+            max_attempts: Some(200),
+            case_sensitive: true,
+            ..Default::default()
+        },
+        |_| {},
+        |_| {},
+    )
+    .unwrap();
+    assert_eq!(metrics.matches_found, 0);
+    assert_eq!(metrics.near_misses, metrics.attempts);
+
+    let metrics = stream_with_handlers(
+        ThreadPoolConfig {
+            pattern: "^z".to_string(),
+            thread_count: 1,
+            max_attempts: Some(200),
+            ..Default::default()
+        },
+        |_| {},
+        |_| {},
+    )
+    .unwrap();
+    assert_eq!(metrics.near_misses, 0);
+}
+
+#[test]
+fn test_zero_threads_is_an_error() {
+    use vanityssh_rust::error::VanityError;
+    use vanityssh_rust::VanitySearch;
+
+    let result = run_thread_pool(ThreadPoolConfig {
+        pattern: "a".to_string(),
+        thread_count: 0,
+        ..Default::default()
+    });
+    match result {
+        Err(VanityError::InvalidFormat(message)) => {
+            assert_eq!(message, "thread count must be >= 1")
+        }
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("a pool without threads was started"),
+    }
+
+    // The library entry points built on the pool fail the same way
+    assert!(VanitySearch::new("a")
+        .threads(0)
+        .run_with_callback(|_| {})
+        .is_err());
+    assert!(keygen::find_matching_key("a", false, None, 0).is_err());
+}
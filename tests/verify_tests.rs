@@ -0,0 +1,100 @@
+// tests/verify_tests.rs
+
+#[cfg(feature = "cli")]
+use assert_cmd::prelude::*;
+#[cfg(feature = "cli")]
+use predicates::prelude::*;
+#[cfg(feature = "cli")]
+use std::fs;
+#[cfg(feature = "cli")]
+use std::process::Command;
+use vanityssh_rust::error::VanityError;
+use vanityssh_rust::{keygen, verify};
+
+#[test]
+fn test_verify_matching_pair() {
+    let (public_key, private_key) = keygen::generate_openssh_key_pair(Some("me@host")).unwrap();
+
+    assert!(verify::verify_key_pair(&private_key, &public_key).is_ok());
+}
+
+#[test]
+fn test_verify_mismatched_pair() {
+    let (_, private_key) = keygen::generate_openssh_key_pair(None).unwrap();
+    let (other_public_key, _) = keygen::generate_openssh_key_pair(None).unwrap();
+
+    let result = verify::verify_key_pair(&private_key, &other_public_key);
+    assert!(matches!(result, Err(VanityError::KeyMismatch(_))));
+}
+
+#[test]
+fn test_verify_malformed_private_key() {
+    let (public_key, _) = keygen::generate_openssh_key_pair(None).unwrap();
+
+    let result = verify::verify_key_pair("not a private key", &public_key);
+    assert!(matches!(result, Err(VanityError::InvalidFormat(_))));
+}
+
+#[test]
+fn test_verify_missing_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let missing = dir.path().join("missing");
+
+    let result = verify::verify_key_pair_files(&missing, &missing);
+    assert!(matches!(result, Err(VanityError::KeyFileError(_))));
+}
+
+#[test]
+#[cfg(feature = "cli")]
+fn test_cli_verify_pair() {
+    let dir = tempfile::tempdir().unwrap();
+    let (public_key, private_key) = keygen::generate_openssh_key_pair(None).unwrap();
+    let (other_public_key, _) = keygen::generate_openssh_key_pair(None).unwrap();
+
+    fs::write(dir.path().join("id_ed25519"), &private_key).unwrap();
+    fs::write(dir.path().join("id_ed25519.pub"), &public_key).unwrap();
+    fs::write(dir.path().join("other.pub"), &other_public_key).unwrap();
+
+    Command::cargo_bin("vanityssh-rust")
+        .unwrap()
+        .arg("--verify-pair")
+        .arg(dir.path().join("id_ed25519"))
+        .arg(dir.path().join("id_ed25519.pub"))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("OK"));
+
+    Command::cargo_bin("vanityssh-rust")
+        .unwrap()
+        .arg("--verify-pair")
+        .arg(dir.path().join("id_ed25519"))
+        .arg(dir.path().join("other.pub"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Key mismatch"));
+}
+
+#[test]
+fn test_verify_signature_of_generated_pair() {
+    let (public_key, private_key) = keygen::generate_openssh_key_pair(None).unwrap();
+    assert!(verify::verify_signature(&private_key, &public_key).is_ok());
+
+    // A signature from one key doesn't verify with another
+    let (other_public, _) = keygen::generate_openssh_key_pair(None).unwrap();
+    assert!(matches!(
+        verify::verify_signature(&private_key, &other_public),
+        Err(VanityError::KeyGenerationError(_))
+    ));
+}
+
+#[test]
+#[cfg(feature = "cli")]
+fn test_cli_verify_checks_each_match() {
+    Command::cargo_bin("vanityssh-rust")
+        .unwrap()
+        .arg(".*")
+        .arg("--verify")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Match found"));
+}